@@ -8,12 +8,8 @@ fn singles(c: &mut Criterion) {
     let mut group = c.benchmark_group("singles");
     for i in [100, 1_000, 10_000, 100_000].iter() {
         group.bench_with_input(BenchmarkId::new("xanadu", i), i, |b, i| {
-            let mut world = xanadu_exclusive_bench::setup(*i);
-            b.iter(|| xanadu_exclusive_bench::benchmark(&mut world));
-        });
-        group.bench_with_input(BenchmarkId::new("xanadu_refcell", i), i, |b, i| {
-            let mut world = xanadu_refcell_bench::setup(*i);
-            b.iter(|| xanadu_refcell_bench::benchmark(&mut world));
+            let mut world = xanadu_bench::setup(*i);
+            b.iter(|| xanadu_bench::benchmark(&mut world));
         });
         group.bench_with_input(BenchmarkId::new("bevy_ecs", i), i, |b, i| {
             let (mut world, mut schedule) = bevy_ecs_bench::setup(*i);
@@ -45,6 +41,10 @@ fn pairs(c: &mut Criterion) {
             let mut world = xanadu_bench::setup(*i);
             b.iter(|| xanadu_bench::benchmark(&mut world));
         });
+        group.bench_with_input(BenchmarkId::new("xanadu_table", i), i, |b, i| {
+            let mut world = xanadu_table_bench::setup(*i);
+            b.iter(|| xanadu_table_bench::benchmark(&mut world));
+        });
         group.bench_with_input(BenchmarkId::new("bevy_ecs", i), i, |b, i| {
             let (mut world, mut schedule) = bevy_ecs_bench::setup(*i);
             b.iter(|| bevy_ecs_bench::benchmark(&mut world, &mut schedule));
@@ -66,5 +66,28 @@ fn pairs(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, singles, pairs);
+fn spawns(c: &mut Criterion) {
+    use benchmark::spawn::*;
+    use xanadu::ecs::StorageKind;
+
+    let mut group = c.benchmark_group("spawns");
+    for i in [100, 1_000, 10_000, 100_000].iter() {
+        group.bench_with_input(BenchmarkId::new("xanadu_each", i), i, |b, i| {
+            b.iter(|| spawn_each(*i, StorageKind::SparseVec));
+        });
+        group.bench_with_input(BenchmarkId::new("xanadu_batch", i), i, |b, i| {
+            b.iter(|| spawn_batch(*i, StorageKind::SparseVec));
+        });
+        group.bench_with_input(BenchmarkId::new("xanadu_table_each", i), i, |b, i| {
+            b.iter(|| spawn_each(*i, StorageKind::Table));
+        });
+        group.bench_with_input(BenchmarkId::new("xanadu_table_batch", i), i, |b, i| {
+            b.iter(|| spawn_batch(*i, StorageKind::Table));
+        });
+    }
+    group.plot_config(PlotConfiguration::default().summary_scale(AxisScale::Logarithmic));
+    group.finish();
+}
+
+criterion_group!(benches, singles, pairs, spawns);
 criterion_main!(benches);
@@ -4,6 +4,7 @@
 
 pub mod pair;
 pub mod single;
+pub mod spawn;
 
 #[inline(always)]
 #[cfg(not(target_arch = "wasm32"))]
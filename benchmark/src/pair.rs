@@ -1,5 +1,3 @@
-use std::cell::RefMut;
-
 use crate::black_box;
 
 pub mod bevy_ecs_bench;
@@ -7,9 +5,10 @@ pub mod game_objects_hash_bench;
 pub mod game_objects_vec_bench;
 pub mod specs_bench;
 pub mod xanadu_bench;
+pub mod xanadu_table_bench;
 
 #[derive(Debug, Clone, PartialEq, Eq, bevy_ecs::prelude::Component)]
-pub struct Id(usize);
+pub struct Id(pub usize);
 
 #[derive(Debug, Clone, PartialEq, bevy_ecs::prelude::Component)]
 pub struct Position {
@@ -37,12 +36,6 @@ pub fn apply_velocity_system(pos: &mut Position, vel: &Velocity) {
     pos.z += vel.z;
 }
 
-pub fn apply_velocity_system_refcell(pos: &mut RefMut<'_, Position>, vel: &RefMut<'_, Velocity>) {
-    pos.x += vel.x;
-    pos.y += vel.y;
-    pos.z += vel.z;
-}
-
 pub fn decay_velocity_system(vel: &mut Velocity) {
     vel.x *= black_box(0.9);
     vel.y *= black_box(0.9);
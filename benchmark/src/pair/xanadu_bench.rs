@@ -1,6 +1,6 @@
-use xanadu::ecs::{PairComponentsRefIterMut, SingleComponentExclusiveIterMut, World};
+use xanadu::ecs::{Query, World};
 
-use super::{apply_velocity_system_refcell, decay_velocity_system, Id, Position, Velocity};
+use super::{apply_velocity_system, decay_velocity_system, Id, Position, Velocity};
 use crate::black_box;
 
 pub fn setup(n: usize) -> World {
@@ -44,15 +44,15 @@ pub fn benchmark(world: &mut World) {
     world.execute(decay_velocity_system_xanadu);
 }
 
-fn apply_velocity_system_xanadu(iter: PairComponentsRefIterMut<'_, Position, Velocity>) {
-    for (mut pos, vel) in iter {
-        apply_velocity_system_refcell(&mut pos, &vel)
+fn apply_velocity_system_xanadu(mut query: Query<'_, (&mut Position, &Velocity)>) {
+    for (mut pos, vel) in query.iter_mut() {
+        apply_velocity_system(&mut pos, vel)
     }
 }
 
-fn decay_velocity_system_xanadu(iter: SingleComponentExclusiveIterMut<'_, Velocity>) {
-    for pos in iter {
-        decay_velocity_system(pos);
+fn decay_velocity_system_xanadu(mut query: Query<'_, &mut Velocity>) {
+    for mut pos in query.iter_mut() {
+        decay_velocity_system(&mut pos);
     }
 }
 
@@ -60,7 +60,6 @@ fn decay_velocity_system_xanadu(iter: SingleComponentExclusiveIterMut<'_, Veloci
 mod test {
     use super::*;
     use crate::pair::{game_objects_vec_bench, GameObject};
-    use xanadu::ecs::SingleComponentExclusiveIter;
 
     #[cfg(target_arch = "wasm32")]
     use wasm_bindgen_test::*;
@@ -90,15 +89,15 @@ mod test {
 
     fn assert_same(game_objects: &[GameObject], world: &mut World) {
         let mut positions = Vec::new();
-        world.execute(|iter: SingleComponentExclusiveIter<'_, Position>| {
-            for pos in iter {
+        world.execute(|query: Query<'_, &Position>| {
+            for pos in query.iter() {
                 positions.push(pos.clone());
             }
         });
 
         let mut velocities = Vec::new();
-        world.execute(|iter: SingleComponentExclusiveIter<'_, Velocity>| {
-            for vel in iter {
+        world.execute(|query: Query<'_, &Velocity>| {
+            for vel in query.iter() {
                 velocities.push(vel.clone());
             }
         });
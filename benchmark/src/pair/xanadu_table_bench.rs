@@ -0,0 +1,120 @@
+use xanadu::ecs::{Query, StorageKind, World};
+
+use super::{apply_velocity_system, decay_velocity_system, Id, Position, Velocity};
+use crate::black_box;
+
+pub fn setup(n: usize) -> World {
+    let mut world = World::builder()
+        .register_component::<Id>()
+        .register_component_with_storage::<Position>(StorageKind::Table)
+        .register_component_with_storage::<Velocity>(StorageKind::Table)
+        .build();
+
+    for i in 0..n {
+        let entity = world.new_entity();
+        world.attach_component(entity, Id(i));
+        if (i / 10) % 3 != 0 {
+            world.attach_component(
+                entity,
+                Position {
+                    x: black_box(i as f64) + 3.0,
+                    y: black_box(i as f64) + 2.0,
+                    z: black_box(i as f64) + 1.0,
+                },
+            );
+        }
+        if (i / 10) % 4 != 0 {
+            world.attach_component(
+                entity,
+                Velocity {
+                    x: black_box(i as f64).mul_add(5.0, 3.0),
+                    y: black_box(i as f64).mul_add(5.0, 2.0),
+                    z: black_box(i as f64).mul_add(5.0, 1.0),
+                },
+            );
+        }
+    }
+
+    world
+}
+
+pub fn benchmark(world: &mut World) {
+    world.execute(decay_velocity_system_xanadu);
+    world.execute(apply_velocity_system_xanadu);
+    world.execute(decay_velocity_system_xanadu);
+}
+
+fn apply_velocity_system_xanadu(mut query: Query<'_, (&mut Position, &Velocity)>) {
+    for (mut pos, vel) in query.iter_mut() {
+        apply_velocity_system(&mut pos, vel)
+    }
+}
+
+fn decay_velocity_system_xanadu(mut query: Query<'_, &mut Velocity>) {
+    for mut pos in query.iter_mut() {
+        decay_velocity_system(&mut pos);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pair::{game_objects_vec_bench, GameObject};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+    #[cfg(all(target_arch = "wasm32", feature = "test_in_browser"))]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    fn setup_test() {
+        let game_objects = game_objects_vec_bench::setup(30);
+        let mut world = setup(30);
+
+        assert_same(&game_objects, &mut world);
+    }
+
+    #[test]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    fn systems_test() {
+        let mut game_objects = game_objects_vec_bench::setup(30);
+        game_objects_vec_bench::benchmark(&mut game_objects);
+
+        let mut world = setup(30);
+        benchmark(&mut world);
+
+        assert_same(&game_objects, &mut world);
+    }
+
+    fn assert_same(game_objects: &[GameObject], world: &mut World) {
+        // テーブルはアーキタイプごとに並ぶので、Id の順に並べ替えてから比較する
+        let mut positions = Vec::new();
+        world.execute(|query: Query<'_, (&Id, &Position)>| {
+            for (id, pos) in query.iter() {
+                positions.push((id.0, pos.clone()));
+            }
+        });
+        positions.sort_by_key(|(id, _)| *id);
+
+        let mut velocities = Vec::new();
+        world.execute(|query: Query<'_, (&Id, &Velocity)>| {
+            for (id, vel) in query.iter() {
+                velocities.push((id.0, vel.clone()));
+            }
+        });
+        velocities.sort_by_key(|(id, _)| *id);
+
+        let expected_positions: Vec<_> = game_objects
+            .iter()
+            .filter_map(|x| Some((x.id, x.position.clone()?)))
+            .collect();
+        assert_eq!(positions, expected_positions);
+
+        let expected_velocities: Vec<_> = game_objects
+            .iter()
+            .filter_map(|x| Some((x.id, x.velocity.clone()?)))
+            .collect();
+        assert_eq!(velocities, expected_velocities);
+    }
+}
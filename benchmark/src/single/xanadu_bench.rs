@@ -1,4 +1,4 @@
-use xanadu::ecs::{SingleComponentExclusiveIterMut, World};
+use xanadu::ecs::{Query, World};
 
 use super::{increment_system, shuffle_system, Id, OtherData, Position};
 use crate::black_box;
@@ -37,15 +37,15 @@ pub fn benchmark(world: &mut World) {
     world.execute(shuffle_system_xanadu);
 }
 
-fn shuffle_system_xanadu(iter: SingleComponentExclusiveIterMut<'_, Position>) {
-    for pos in iter {
-        shuffle_system(pos);
+fn shuffle_system_xanadu(mut query: Query<'_, &mut Position>) {
+    for mut pos in query.iter_mut() {
+        shuffle_system(&mut pos);
     }
 }
 
-fn increment_system_xanadu(iter: SingleComponentExclusiveIterMut<'_, Position>) {
-    for pos in iter {
-        increment_system(pos);
+fn increment_system_xanadu(mut query: Query<'_, &mut Position>) {
+    for mut pos in query.iter_mut() {
+        increment_system(&mut pos);
     }
 }
 
@@ -53,7 +53,6 @@ fn increment_system_xanadu(iter: SingleComponentExclusiveIterMut<'_, Position>)
 mod test {
     use super::*;
     use crate::single::{game_objects_vec_bench, GameObject};
-    use xanadu::ecs::SingleComponentExclusiveIter;
 
     #[cfg(target_arch = "wasm32")]
     use wasm_bindgen_test::*;
@@ -83,8 +82,8 @@ mod test {
 
     fn assert_same(game_objects: &[GameObject], world: &mut World) {
         let mut positions = Vec::new();
-        world.execute(|iter: SingleComponentExclusiveIter<'_, Position>| {
-            for pos in iter {
+        world.execute(|query: Query<'_, &Position>| {
+            for pos in query.iter() {
                 positions.push(pos.clone());
             }
         });
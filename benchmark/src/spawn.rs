@@ -0,0 +1,85 @@
+use xanadu::ecs::{StorageKind, World};
+
+use crate::{
+    black_box,
+    pair::{Id, Position, Velocity},
+};
+
+fn world(kind: StorageKind) -> World {
+    World::builder()
+        .register_component::<Id>()
+        .register_component_with_storage::<Position>(kind)
+        .register_component_with_storage::<Velocity>(kind)
+        .build()
+}
+
+fn bundle(i: usize) -> (Id, Position, Velocity) {
+    let x = black_box(i as f64);
+    (
+        Id(i),
+        Position {
+            x: x + 3.0,
+            y: x + 2.0,
+            z: x + 1.0,
+        },
+        Velocity {
+            x: x.mul_add(5.0, 3.0),
+            y: x.mul_add(5.0, 2.0),
+            z: x.mul_add(5.0, 1.0),
+        },
+    )
+}
+
+/// Spawn the entities one by one with [`World::new_entity`] and [`World::attach_component`].
+pub fn spawn_each(n: usize, kind: StorageKind) -> World {
+    let mut world = world(kind);
+    for i in 0..n {
+        let (id, position, velocity) = bundle(i);
+        let entity = world.new_entity();
+        world.attach_component(entity, id);
+        world.attach_component(entity, position);
+        world.attach_component(entity, velocity);
+    }
+    world
+}
+
+/// Spawn the entities at once with [`World::spawn_batch`].
+pub fn spawn_batch(n: usize, kind: StorageKind) -> World {
+    let mut world = world(kind);
+    world.spawn_batch((0..n).map(bundle));
+    world
+}
+
+#[cfg(test)]
+mod test {
+    use xanadu::ecs::Query;
+
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+    #[cfg(all(target_arch = "wasm32", feature = "test_in_browser"))]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    fn same_entities() {
+        for kind in [StorageKind::SparseVec, StorageKind::Table] {
+            assert_eq!(
+                collect(&mut spawn_each(30, kind)),
+                collect(&mut spawn_batch(30, kind))
+            );
+        }
+    }
+
+    fn collect(world: &mut World) -> Vec<(Id, Position, Velocity)> {
+        let mut items = Vec::new();
+        world.execute(|query: Query<'_, (&Id, &Position, &Velocity)>| {
+            for (id, pos, vel) in query.iter() {
+                items.push((id.clone(), pos.clone(), vel.clone()));
+            }
+        });
+        items.sort_by_key(|(id, _, _)| id.0);
+        items
+    }
+}
@@ -0,0 +1,96 @@
+#![deny(rust_2018_idioms)]
+#![deny(clippy::all)]
+#![deny(clippy::nursery)]
+
+//! Derive macros for [Xanadu](https://docs.rs/xanadu). Use them through `xanadu::ecs`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Index};
+
+/// Derive `xanadu::ecs::Bundle` for a struct whose fields are components.
+///
+/// See the documentation of `Bundle` in Xanadu.
+#[proc_macro_derive(Bundle)]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    bundle(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn bundle(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "Bundle can only be derived for structs",
+        ));
+    };
+    let types: Vec<_> = data.fields.iter().map(|field| &field.ty).collect();
+    // 同じ型のコンポーネントは一つのエンティティに一つしか付けられない
+    for (i, ty) in types.iter().enumerate() {
+        let name = quote!(#ty).to_string();
+        if types[..i]
+            .iter()
+            .any(|other| quote!(#other).to_string() == name)
+        {
+            return Err(syn::Error::new_spanned(
+                ty,
+                format!(
+                    "component type `{}` appears more than once in the bundle",
+                    name
+                ),
+            ));
+        }
+    }
+    // 名前付きのフィールドならその名前を、タプル構造体なら番号を使う
+    let members: Vec<TokenStream2> = match &data.fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let ident = &field.ident;
+                quote!(#ident)
+            })
+            .collect(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|index| {
+                let index = Index::from(index);
+                quote!(#index)
+            })
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+    let vars: Vec<_> = (0..members.len())
+        .map(|index| quote::format_ident!("field_{}", index))
+        .collect();
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::xanadu::ecs::Bundle for #name #ty_generics #where_clause {
+            fn component_types(
+                types: &mut ::std::vec::Vec<(::std::any::TypeId, &'static str)>,
+            ) {
+                #(types.push((
+                    ::std::any::TypeId::of::<#types>(),
+                    ::std::any::type_name::<#types>(),
+                ));)*
+            }
+
+            fn write(self, writer: &mut ::xanadu::ecs::BundleWriter<'_>) {
+                #(writer.write(self.#members);)*
+            }
+
+            fn take(
+                reader: &mut ::xanadu::ecs::BundleReader<'_>,
+            ) -> ::std::option::Option<Self> {
+                #(let #vars = reader.take::<#types>();)*
+                ::std::option::Option::Some(Self {
+                    #(#members: #vars?,)*
+                })
+            }
+        }
+    })
+}
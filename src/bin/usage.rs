@@ -1,7 +1,4 @@
-use xanadu::ecs::{
-    PairComponentsRefIter, PairComponentsRefIterMut, SingleComponentExclusiveIter,
-    SingleComponentExclusiveIterMut, World,
-};
+use xanadu::ecs::{Query, World};
 
 #[derive(Debug)]
 pub struct Position {
@@ -66,14 +63,14 @@ fn main() {
     world.execute(print2_system);
 }
 
-fn print_system(iter: SingleComponentExclusiveIter<'_, Position>) {
-    for pos in iter {
+fn print_system(query: Query<'_, &Position>) {
+    for pos in query.iter() {
         println!("Pos: [{}, {}, {}]", pos.x, pos.y, pos.z);
     }
 }
 
-fn shuffle_system(iter: SingleComponentExclusiveIterMut<'_, Position>) {
-    for pos in iter {
+fn shuffle_system(mut query: Query<'_, &mut Position>) {
+    for mut pos in query.iter_mut() {
         let tmp = pos.x;
         pos.x = pos.y;
         pos.y = pos.z;
@@ -81,16 +78,16 @@ fn shuffle_system(iter: SingleComponentExclusiveIterMut<'_, Position>) {
     }
 }
 
-fn increment_system(iter: SingleComponentExclusiveIterMut<'_, Position>) {
-    for pos in iter {
+fn increment_system(mut query: Query<'_, &mut Position>) {
+    for mut pos in query.iter_mut() {
         pos.x += 1.0;
         pos.y += 2.0;
         pos.z += 3.0;
     }
 }
 
-fn print2_system(iter: PairComponentsRefIter<'_, Position, Velocity>) {
-    for (pos, vel) in iter {
+fn print2_system(query: Query<'_, (&Position, &Velocity)>) {
+    for (pos, vel) in query.iter() {
         println!(
             "Pos: [{}, {}, {}] Vel: [{}, {}, {}]",
             pos.x, pos.y, pos.z, vel.x, vel.y, vel.z
@@ -98,8 +95,8 @@ fn print2_system(iter: PairComponentsRefIter<'_, Position, Velocity>) {
     }
 }
 
-fn apply_velocity_system(iter: PairComponentsRefIterMut<'_, Position, Velocity>) {
-    for (mut pos, vel) in iter {
+fn apply_velocity_system(mut query: Query<'_, (&mut Position, &Velocity)>) {
+    for (mut pos, vel) in query.iter_mut() {
         pos.x += vel.x;
         pos.y += vel.y;
         pos.z += vel.z;
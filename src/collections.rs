@@ -1,7 +1,9 @@
+mod atomic_ref_cell;
+mod generational_sparse_vec;
 mod generational_vec;
 mod sparse_vec;
-mod type_erased_sparse_vec;
 
+pub use atomic_ref_cell::*;
+pub use generational_sparse_vec::*;
 pub use generational_vec::*;
 pub use sparse_vec::*;
-pub use type_erased_sparse_vec::*;
@@ -0,0 +1,330 @@
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// 可変借用中はこのビットが立つ。下位ビットは共有借用の数
+const WRITING: usize = !(usize::MAX >> 1);
+
+/// Thread-safe version of [`RefCell`](std::cell::RefCell)
+///
+/// Borrows are checked at runtime with an atomic counter, so the cell can be shared between
+/// threads. Unlike `RefCell`, borrowing never blocks nor panics; the `try_*` methods return an
+/// error instead.
+pub struct AtomicRefCell<T: ?Sized> {
+    state: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY:
+// 共有借用は &T を、可変借用は &mut T を他のスレッドに渡すので、それぞれ Sync と Send が必要
+unsafe impl<T: ?Sized + Send> Send for AtomicRefCell<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for AtomicRefCell<T> {}
+
+/// Error returned when an [`AtomicRefCell`] is already borrowed in a conflicting way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowError;
+
+impl<T> AtomicRefCell<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> AtomicRefCell<T> {
+    /// Borrow the value immutably, or return an error if it is mutably borrowed.
+    pub fn try_borrow(&self) -> Result<AtomicRef<'_, T>, BorrowError> {
+        // 可変借用中は状態に触らない。失敗した借用が数を戻すと、他の借用と競合するため
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            // 共有借用の数が WRITING ビットに達する場合も拒否する
+            if state & WRITING != 0 || state + 1 == WRITING {
+                return Err(BorrowError);
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(actual) => state = actual,
+            }
+        }
+        Ok(AtomicRef {
+            // SAFETY: 可変借用がないことを確認した
+            value: unsafe { &*self.value.get() },
+            state: &self.state,
+        })
+    }
+
+    /// Borrow the value mutably, or return an error if it is borrowed.
+    pub fn try_borrow_mut(&self) -> Result<AtomicRefMut<'_, T>, BorrowError> {
+        self.state
+            .compare_exchange(0, WRITING, Ordering::Acquire, Ordering::Relaxed)
+            .map_err(|_| BorrowError)?;
+        Ok(AtomicRefMut {
+            // SAFETY: UnsafeCell::get は null を返さない
+            value: unsafe { NonNull::new_unchecked(self.value.get()) },
+            state: &self.state,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Get a mutable reference to the value without checking borrows, which is statically safe.
+    pub const fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: Default> Default for AtomicRefCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for AtomicRefCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_borrow() {
+            Ok(value) => f.debug_tuple("AtomicRefCell").field(&&*value).finish(),
+            Err(_) => f.write_str("AtomicRefCell(<borrowed>)"),
+        }
+    }
+}
+
+/// Immutable borrow of an [`AtomicRefCell`]
+pub struct AtomicRef<'a, T: ?Sized> {
+    value: &'a T,
+    state: &'a AtomicUsize,
+}
+
+impl<'a, T: ?Sized> AtomicRef<'a, T> {
+    /// Make a new borrow for a part of the borrowed value.
+    pub fn map<U: ?Sized>(orig: Self, f: impl FnOnce(&T) -> &U) -> AtomicRef<'a, U> {
+        let value = f(orig.value);
+        let state = orig.state;
+        std::mem::forget(orig);
+        AtomicRef { value, state }
+    }
+
+    /// Make a new borrow for an optional part of the borrowed value, or return the original borrow
+    /// if the closure returns None.
+    pub fn filter_map<U: ?Sized>(
+        orig: Self,
+        f: impl FnOnce(&T) -> Option<&U>,
+    ) -> Result<AtomicRef<'a, U>, Self> {
+        match f(orig.value) {
+            Some(value) => {
+                let state = orig.state;
+                std::mem::forget(orig);
+                Ok(AtomicRef { value, state })
+            }
+            None => Err(orig),
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for AtomicRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<T: ?Sized> Drop for AtomicRef<'_, T> {
+    fn drop(&mut self) {
+        self.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for AtomicRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// Mutable borrow of an [`AtomicRefCell`]
+pub struct AtomicRefMut<'a, T: ?Sized> {
+    value: NonNull<T>,
+    state: &'a AtomicUsize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+// SAFETY: &'a mut T と同じ
+unsafe impl<T: ?Sized + Send> Send for AtomicRefMut<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for AtomicRefMut<'_, T> {}
+
+impl<'a, T: ?Sized> AtomicRefMut<'a, T> {
+    /// Make a new borrow for a part of the borrowed value.
+    pub fn map<U: ?Sized>(mut orig: Self, f: impl FnOnce(&mut T) -> &mut U) -> AtomicRefMut<'a, U> {
+        let value = NonNull::from(f(&mut *orig));
+        let state = orig.state;
+        std::mem::forget(orig);
+        AtomicRefMut {
+            value,
+            state,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Make a new borrow for an optional part of the borrowed value, or return the original borrow
+    /// if the closure returns None.
+    pub fn filter_map<U: ?Sized>(
+        mut orig: Self,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> Result<AtomicRefMut<'a, U>, Self> {
+        // SAFETY: orig が生きている間は借用が有効
+        let value = unsafe { orig.value.as_mut() };
+        match f(value) {
+            Some(value) => {
+                let value = NonNull::from(value);
+                let state = orig.state;
+                std::mem::forget(orig);
+                Ok(AtomicRefMut {
+                    value,
+                    state,
+                    _marker: PhantomData,
+                })
+            }
+            None => Err(orig),
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for AtomicRefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: 可変借用を持っているので、他に参照は存在しない
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for AtomicRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: 可変借用を持っているので、他に参照は存在しない
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for AtomicRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.state.fetch_and(!WRITING, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for AtomicRefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shared_borrows() {
+        let cell = AtomicRefCell::new(1);
+        let a = cell.try_borrow().unwrap();
+        let b = cell.try_borrow().unwrap();
+        assert_eq!(*a + *b, 2);
+        assert_eq!(cell.try_borrow_mut().err(), Some(BorrowError));
+        drop(a);
+        assert!(cell.try_borrow_mut().is_err());
+        drop(b);
+        assert!(cell.try_borrow_mut().is_ok());
+    }
+
+    #[test]
+    fn exclusive_borrow() {
+        let cell = AtomicRefCell::new(1);
+        let mut a = cell.try_borrow_mut().unwrap();
+        *a += 1;
+        assert!(cell.try_borrow().is_err());
+        assert!(cell.try_borrow_mut().is_err());
+        drop(a);
+        assert_eq!(*cell.try_borrow().unwrap(), 2);
+    }
+
+    #[test]
+    fn map() {
+        let cell = AtomicRefCell::new(vec![1, 2, 3]);
+        let slice = AtomicRef::map(cell.try_borrow().unwrap(), |vec| &vec[1..]);
+        assert_eq!(&*slice, &[2, 3]);
+        assert!(AtomicRef::filter_map(slice, |slice| slice.get(5)).is_err());
+        assert!(cell.try_borrow_mut().is_ok());
+
+        let mut first = AtomicRefMut::map(cell.try_borrow_mut().unwrap(), |vec| &mut vec[0]);
+        *first = 10;
+        assert!(cell.try_borrow().is_err());
+        drop(first);
+        assert_eq!(*cell.try_borrow().unwrap(), vec![10, 2, 3]);
+    }
+
+    #[test]
+    fn threads() {
+        let cell = AtomicRefCell::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..1000 {
+                        if let Ok(mut value) = cell.try_borrow_mut() {
+                            *value += 1;
+                        }
+                        let _ = cell.try_borrow().map(|value| *value);
+                    }
+                });
+            }
+        });
+        assert!(*cell.try_borrow().unwrap() <= 4000);
+    }
+
+    #[test]
+    fn concurrent_readers_and_writers() {
+        // 借用の外で数えた借用の数が、セルの規則を満たしていることを確かめる
+        let cell = AtomicRefCell::new(());
+        let readers = AtomicUsize::new(0);
+        let writers = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let (cell, readers, writers) = (&cell, &readers, &writers);
+                scope.spawn(move || {
+                    for i in 0..50000 {
+                        if (thread + i) % 4 == 0 {
+                            if let Ok(borrow) = cell.try_borrow_mut() {
+                                assert_eq!(writers.fetch_add(1, Ordering::SeqCst), 0);
+                                assert_eq!(readers.load(Ordering::SeqCst), 0);
+                                std::thread::yield_now();
+                                assert_eq!(readers.load(Ordering::SeqCst), 0);
+                                writers.fetch_sub(1, Ordering::SeqCst);
+                                drop(borrow);
+                            }
+                        } else if let Ok(borrow) = cell.try_borrow() {
+                            readers.fetch_add(1, Ordering::SeqCst);
+                            assert_eq!(writers.load(Ordering::SeqCst), 0);
+                            std::thread::yield_now();
+                            assert_eq!(writers.load(Ordering::SeqCst), 0);
+                            readers.fetch_sub(1, Ordering::SeqCst);
+                            drop(borrow);
+                        }
+                    }
+                });
+            }
+        });
+        // 借用が全て返されていれば、再び可変借用できる
+        assert!(cell.try_borrow_mut().is_ok());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::collections::{GenerationalId, SparseVec};
+
+/// Sparse collection keyed by [`GenerationalId`]s
+///
+/// Each slot remembers the generation of the ID which inserted the element, so an ID that is older
+/// than the current owner of the slot can neither read nor overwrite the element.
+pub struct GenerationalSparseVec<T> {
+    data: SparseVec<T>,
+    // data[i] を所有しているIDの世代
+    generations: Vec<u32>,
+}
+
+impl<T> GenerationalSparseVec<T> {
+    pub const fn new() -> Self {
+        Self {
+            data: SparseVec::new(),
+            generations: Vec::new(),
+        }
+    }
+
+    /// Reserve capacity so that the IDs whose indices are less than `len` can be inserted without
+    /// reallocation.
+    pub fn reserve_indices(&mut self, len: usize) {
+        self.data.reserve_indices(len);
+        self.generations
+            .reserve(len.saturating_sub(self.generations.len()));
+    }
+
+    /// Add an element to the collection at the slot of the ID. Extend the array as needed.
+    ///
+    /// An element owned by an older generation of the slot is dropped and replaced.
+    ///
+    /// # Returns
+    ///
+    /// Returns the previous element of the same ID, if any. Otherwise, returns `Ok(None)`.
+    ///
+    /// Returns `Err(value)` without modifying the collection if the slot has been owned by a newer
+    /// generation than the ID, even if the element of that generation has been removed.
+    pub fn insert(&mut self, id: GenerationalId, value: T) -> Result<Option<T>, T> {
+        if id.index >= self.generations.len() {
+            self.generations.resize(id.index + 1, 0);
+        }
+        let generation = self.generations[id.index];
+        // 空いているスロットでも、世代を巻き戻さない
+        if generation > id.generation {
+            return Err(value);
+        }
+        self.generations[id.index] = id.generation;
+        let previous = self.data.replace(id.index, value);
+        if generation == id.generation {
+            Ok(previous)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get a reference to the element of the ID.
+    pub fn get(&self, id: GenerationalId) -> Option<&T> {
+        if self.owns(id) {
+            self.data.get(id.index)
+        } else {
+            None
+        }
+    }
+
+    /// Get a mutable reference to the element of the ID.
+    pub fn get_mut(&mut self, id: GenerationalId) -> Option<&mut T> {
+        if self.owns(id) {
+            self.data.get_mut(id.index)
+        } else {
+            None
+        }
+    }
+
+    /// Remove the element of the ID, if one exists.
+    ///
+    /// # Returns
+    ///
+    /// Returns the previous element, if any. Otherwise, returns None.
+    pub fn remove(&mut self, id: GenerationalId) -> Option<T> {
+        if self.owns(id) {
+            self.data.remove(id.index)
+        } else {
+            None
+        }
+    }
+
+    /// Remove the element at the specified index regardless of its generation.
+    pub fn remove_index(&mut self, index: usize) -> Option<T> {
+        self.data.remove(index)
+    }
+
+    /// Check if the ID has an element in the collection.
+    pub fn contains(&self, id: GenerationalId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Returns an iterator over the elements.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.data.iter()
+    }
+
+    /// Returns a mutable iterator over the elements.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.data.iter_mut()
+    }
+
+    fn owns(&self, id: GenerationalId) -> bool {
+        self.generations.get(id.index) == Some(&id.generation)
+    }
+
+    pub(crate) fn data_slice(&self) -> &[Option<T>] {
+        self.data.data_slice()
+    }
+
+    pub(crate) fn data_mut_slice(&mut self) -> &mut [Option<T>] {
+        self.data.data_mut_slice()
+    }
+
+    /// `data_slice()[i]` を所有しているIDの世代
+    pub(crate) fn generations_slice(&self) -> &[u32] {
+        &self.generations
+    }
+}
+
+impl<T> Default for GenerationalSparseVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::collections::GenerationalVec;
+
+    #[test]
+    fn insert_and_get() {
+        let mut ids = GenerationalVec::new();
+        let id1 = ids.add(());
+        let id2 = ids.add(());
+        let mut v = GenerationalSparseVec::new();
+        assert_eq!(v.insert(id2, 42), Ok(None));
+        assert_eq!(v.insert(id2, 43), Ok(Some(42)));
+        assert_eq!(v.get(id1), None);
+        assert_eq!(v.get(id2), Some(&43));
+    }
+
+    #[test]
+    fn stale_id() {
+        let mut ids = GenerationalVec::new();
+        let old = ids.add(());
+        ids.remove(old);
+        let new = ids.add(());
+        assert_eq!(old.index, new.index);
+
+        let mut v = GenerationalSparseVec::new();
+        assert_eq!(v.insert(new, 42), Ok(None));
+        assert_eq!(v.insert(old, 43), Err(43));
+        assert_eq!(v.get(old), None);
+        assert_eq!(v.get_mut(old), None);
+        assert_eq!(v.remove(old), None);
+        assert!(!v.contains(old));
+        assert_eq!(v.get(new), Some(&42));
+    }
+
+    #[test]
+    fn stale_id_into_empty_slot() {
+        let mut ids = GenerationalVec::new();
+        let old = ids.add(());
+        ids.remove(old);
+        let new = ids.add(());
+
+        let mut v = GenerationalSparseVec::new();
+        v.insert(new, 42).unwrap();
+        assert_eq!(v.remove(new), Some(42));
+        assert_eq!(v.insert(old, 43), Err(43));
+        assert_eq!(v.get(old), None);
+        assert_eq!(v.insert(new, 44), Ok(None));
+        assert_eq!(v.get(new), Some(&44));
+    }
+
+    #[test]
+    fn newer_id_replaces_older_owner() {
+        let mut ids = GenerationalVec::new();
+        let old = ids.add(());
+        let mut v = GenerationalSparseVec::new();
+        assert_eq!(v.insert(old, 42), Ok(None));
+
+        ids.remove(old);
+        let new = ids.add(());
+        assert_eq!(v.get(new), None);
+        assert_eq!(v.insert(new, 43), Ok(None));
+        assert_eq!(v.get(old), None);
+        assert_eq!(v.get(new), Some(&43));
+    }
+
+    #[test]
+    fn remove() {
+        let mut ids = GenerationalVec::new();
+        let id = ids.add(());
+        let mut v = GenerationalSparseVec::new();
+        v.insert(id, 42).unwrap();
+        assert_eq!(v.remove(id), Some(42));
+        assert_eq!(v.remove(id), None);
+        assert!(!v.contains(id));
+    }
+
+    #[test]
+    fn remove_index() {
+        let mut ids = GenerationalVec::new();
+        let id = ids.add(());
+        let mut v = GenerationalSparseVec::new();
+        v.insert(id, 42).unwrap();
+        assert_eq!(v.remove_index(id.index), Some(42));
+        assert_eq!(v.get(id), None);
+    }
+}
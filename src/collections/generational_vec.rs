@@ -4,7 +4,7 @@ use std::collections::VecDeque;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct GenerationalId {
     pub(crate) index: usize,
-    generation: u32,
+    pub(crate) generation: u32,
 }
 
 struct Entry<T> {
@@ -37,12 +37,45 @@ impl<T> GenerationalVec<T> {
         }
     }
 
+    /// Reserve capacity for at least `additional` more elements, taking the empty slots into
+    /// account
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries
+            .reserve(additional.saturating_sub(self.empty_queue.len()));
+    }
+
+    /// インデックスの上限を返す
+    pub(crate) const fn index_len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 要素を `additional` 個追加した後のインデックスの上限を返す
+    pub(crate) fn index_len_after(&self, additional: usize) -> usize {
+        self.index_len() + additional.saturating_sub(self.empty_queue.len())
+    }
+
+    /// 空いているスロットを使わずに、末尾に要素を追加する
+    pub(crate) fn push(&mut self, value: T) -> GenerationalId {
+        let index = self.entries.len();
+        self.entries.push(Entry {
+            generation: 0,
+            inner: Some(value),
+        });
+        GenerationalId {
+            index,
+            generation: 0,
+        }
+    }
+
     /// Remove an element from the collection
     pub fn remove(&mut self, id: GenerationalId) -> Option<T> {
         if let Some(entry) = self.entries.get_mut(id.index) {
             if entry.generation == id.generation {
                 let value = entry.inner.take();
-                self.empty_queue.push_back(id.index);
+                // 既に削除済みの要素を再度キューに入れないようにする
+                if value.is_some() {
+                    self.empty_queue.push_back(id.index);
+                }
                 return value;
             }
         }
@@ -96,6 +129,16 @@ impl<T> GenerationalVec<T> {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// 指定したインデックスのスロットに要素が存在すれば、そのIDを返す
+    pub(crate) fn id_at(&self, index: usize) -> Option<GenerationalId> {
+        let entry = self.entries.get(index)?;
+        entry.inner.as_ref()?;
+        Some(GenerationalId {
+            index,
+            generation: entry.generation,
+        })
+    }
 }
 
 impl<T> Default for GenerationalVec<T> {
@@ -119,6 +162,19 @@ mod test {
         assert_eq!(v.remove(id3), Some(300));
     }
 
+    #[test]
+    fn remove_twice() {
+        let mut v = GenerationalVec::new();
+        let id = v.add(100);
+        assert_eq!(v.remove(id), Some(100));
+        assert_eq!(v.remove(id), None);
+        assert_eq!(v.len(), 0);
+
+        let id1 = v.add(200);
+        let id2 = v.add(300);
+        assert_ne!(id1.index, id2.index);
+    }
+
     #[test]
     fn get() {
         let mut v = GenerationalVec::new();
@@ -194,4 +250,16 @@ mod test {
         v.remove(id);
         assert!(v.is_empty());
     }
+
+    #[test]
+    fn reserve() {
+        let mut v = GenerationalVec::new();
+        let id = v.add(100);
+        v.add(200);
+        v.remove(id);
+        // 空いているスロットが 1 つあるので、新しく必要なのは 2 つ
+        assert_eq!(v.index_len_after(3), 4);
+        v.reserve(3);
+        assert!(v.entries.capacity() >= 4);
+    }
 }
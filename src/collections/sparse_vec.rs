@@ -20,6 +20,11 @@ impl<T> SparseVec<T> {
         self.data[index].replace(component)
     }
 
+    /// Reserve capacity so that the indices less than `len` can be used without reallocation.
+    pub fn reserve_indices(&mut self, len: usize) {
+        self.data.reserve(len.saturating_sub(self.data.len()));
+    }
+
     /// Get an element at the specified index.
     ///
     // TODO: テスト
@@ -52,10 +57,6 @@ impl<T> SparseVec<T> {
         self.data.iter_mut().filter_map(|v| v.as_mut())
     }
 
-    pub(crate) fn data_iter_mut(&mut self) -> std::slice::IterMut<'_, Option<T>> {
-        self.data.iter_mut()
-    }
-
     pub(crate) fn data_slice(&self) -> &[Option<T>] {
         &self.data
     }
@@ -1,12 +1,44 @@
+mod access;
+mod archetype;
+mod bundle;
+mod change;
+mod command;
 mod component;
-mod iter;
+mod entity;
+mod event;
+mod filter;
+mod hierarchy;
+mod hook;
+mod observer;
+mod query;
+mod relation;
+mod removal;
+mod resource;
+mod schedule;
+mod storage;
 mod system;
 mod world;
 
-pub use component::Component;
-pub use iter::{
-    FromWorld, PairComponentsRefIter, PairComponentsRefIterMut, SingleComponentExclusiveIter,
-    SingleComponentExclusiveIterMut, SingleComponentRefIter, SingleComponentRefIterMut,
-};
-pub use system::System;
+pub use access::Access;
+pub use archetype::{Archetype, EntityLocation};
+pub use bundle::{Bundle, BundleReader, BundleWriter};
+pub use change::{Mut, SystemTicks, Tick};
+pub use command::{Commands, EntityCommands};
+pub use component::{Component, MaybeSend, MaybeSendSync};
+pub use entity::Entity;
+pub use event::{Event, EventReader, EventWriter, Events};
+pub use filter::{Added, Changed, Or, QueryFilter, With, Without};
+pub use hierarchy::{Ancestors, Children, Descendants, Hierarchy, Parent};
+pub use hook::Hook;
+pub use observer::{Traversal, Trigger};
+#[cfg(feature = "parallel")]
+pub use query::QueryParIter;
+pub use query::{Query, QueryData, QueryIter, ReadOnlyQueryData};
+pub use relation::{Cleanup, Related, Relation, RelationPolicy};
+pub use removal::{RemovedComponents, RemovedIter};
+pub use resource::{Res, ResMut, Resource};
+pub use schedule::{Schedule, SystemConfig};
+pub use storage::StorageKind;
+pub use system::{FromWorld, System};
 pub use world::{World, WorldBuilder};
+pub use xanadu_macros::Bundle;
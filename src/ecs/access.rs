@@ -0,0 +1,181 @@
+use std::any::{type_name, TypeId};
+
+use super::{Component, Resource};
+use crate::Error;
+
+#[derive(Debug, Clone, Copy)]
+struct ComponentAccess {
+    type_id: TypeId,
+    name: &'static str,
+    // コンポーネントとリソースは同じ型でも別物として扱う
+    resource: bool,
+    mutable: bool,
+}
+
+impl ComponentAccess {
+    fn same_target(&self, other: &Self) -> bool {
+        self.type_id == other.type_id && self.resource == other.resource
+    }
+}
+
+/// Set of components and resources which a system parameter reads or writes
+///
+/// This is declared by [`FromWorld::access`](crate::ecs::FromWorld::access), and used to check that
+/// the parameters of a system do not alias each other mutably.
+#[derive(Debug, Clone, Default)]
+pub struct Access {
+    components: Vec<ComponentAccess>,
+    // extend で見つかった最初の競合
+    conflict: Option<ComponentAccess>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare that the component `T` is read.
+    pub fn add_read<T: Component>(&mut self) {
+        self.add(ComponentAccess {
+            type_id: TypeId::of::<T>(),
+            name: type_name::<T>(),
+            resource: false,
+            mutable: false,
+        });
+    }
+
+    /// Declare that the component `T` is written.
+    pub fn add_write<T: Component>(&mut self) {
+        self.add(ComponentAccess {
+            type_id: TypeId::of::<T>(),
+            name: type_name::<T>(),
+            resource: false,
+            mutable: true,
+        });
+    }
+
+    /// Declare that the resource `T` is read.
+    pub fn add_resource_read<T: Resource>(&mut self) {
+        self.add(ComponentAccess {
+            type_id: TypeId::of::<T>(),
+            name: type_name::<T>(),
+            resource: true,
+            mutable: false,
+        });
+    }
+
+    /// Declare that the resource `T` is written.
+    pub fn add_resource_write<T: Resource>(&mut self) {
+        self.add(ComponentAccess {
+            type_id: TypeId::of::<T>(),
+            name: type_name::<T>(),
+            resource: true,
+            mutable: true,
+        });
+    }
+
+    fn add(&mut self, access: ComponentAccess) {
+        match self.components.iter_mut().find(|a| a.same_target(&access)) {
+            Some(existing) => existing.mutable |= access.mutable,
+            None => self.components.push(access),
+        }
+    }
+
+    /// Merge the access of another parameter into this one, recording a conflict if either of them
+    /// writes a component or resource which the other accesses.
+    pub fn extend(&mut self, other: &Self) {
+        if self.conflict.is_none() {
+            self.conflict = self.find_conflict(other).or(other.conflict);
+        }
+        for access in &other.components {
+            self.add(*access);
+        }
+    }
+
+    /// Returns the name of a component or resource which is written by one of the accesses and
+    /// accessed by the other, if any.
+    pub fn conflict_with(&self, other: &Self) -> Option<&'static str> {
+        self.find_conflict(other).map(|access| access.name)
+    }
+
+    fn find_conflict(&self, other: &Self) -> Option<ComponentAccess> {
+        self.components.iter().copied().find(|a| {
+            other
+                .components
+                .iter()
+                .any(|b| a.same_target(b) && (a.mutable || b.mutable))
+        })
+    }
+
+    /// Check if no conflict was found while merging the accesses of the parameters.
+    pub const fn check(&self) -> Result<(), Error> {
+        match self.conflict {
+            Some(access) if access.resource => Err(Error::ConflictingResourceAccess(access.name)),
+            Some(access) => Err(Error::ConflictingAccess(access.name)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_do_not_conflict() {
+        let mut a = Access::new();
+        a.add_read::<i32>();
+        let mut b = Access::new();
+        b.add_read::<i32>();
+        b.add_write::<f32>();
+        assert_eq!(a.conflict_with(&b), None);
+        a.extend(&b);
+        assert_eq!(a.check(), Ok(()));
+    }
+
+    #[test]
+    fn write_conflicts_with_read() {
+        let mut a = Access::new();
+        a.add_write::<i32>();
+        let mut b = Access::new();
+        b.add_read::<f32>();
+        b.add_read::<i32>();
+        assert_eq!(a.conflict_with(&b), Some("i32"));
+        assert_eq!(b.conflict_with(&a), Some("i32"));
+        a.extend(&b);
+        assert_eq!(a.check(), Err(Error::ConflictingAccess("i32")));
+    }
+
+    #[test]
+    fn access_within_one_parameter_is_not_a_conflict() {
+        let mut a = Access::new();
+        a.add_read::<i32>();
+        a.add_write::<i32>();
+        assert_eq!(a.check(), Ok(()));
+    }
+
+    #[test]
+    fn conflict_is_kept_after_extend() {
+        let mut a = Access::new();
+        a.add_write::<i32>();
+        let mut b = Access::new();
+        b.add_write::<i32>();
+        a.extend(&b);
+        let mut c = Access::new();
+        c.extend(&a);
+        assert_eq!(c.check(), Err(Error::ConflictingAccess("i32")));
+    }
+
+    #[test]
+    fn resources_and_components_are_distinct() {
+        let mut a = Access::new();
+        a.add_write::<i32>();
+        let mut b = Access::new();
+        b.add_resource_write::<i32>();
+        assert_eq!(a.conflict_with(&b), None);
+        let mut c = Access::new();
+        c.add_resource_read::<i32>();
+        b.extend(&c);
+        assert_eq!(b.check(), Err(Error::ConflictingResourceAccess("i32")));
+    }
+}
@@ -0,0 +1,279 @@
+use std::{any::TypeId, collections::HashMap};
+
+use super::{Component, Entity};
+
+/// Location of an entity, which is passed to [`QueryData::fetch`](crate::ecs::QueryData::fetch)
+///
+/// Components stored in [`StorageKind::SparseVec`](crate::ecs::StorageKind::SparseVec) are looked
+/// up by the index and the generation of the entity, and components stored in
+/// [`StorageKind::Table`](crate::ecs::StorageKind::Table) are looked up by the archetype and the
+/// row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityLocation {
+    pub(crate) index: usize,
+    pub(crate) generation: u32,
+    pub(crate) archetype: usize,
+    pub(crate) row: usize,
+}
+
+impl EntityLocation {
+    /// Returns the entity at the location.
+    pub(crate) const fn entity(&self) -> Entity {
+        Entity {
+            index: self.index,
+            generation: self.generation,
+        }
+    }
+}
+
+/// Group of entities which have the same set of table components
+///
+/// The table components of the entities in an archetype are stored contiguously, in the same order
+/// as [`Archetype::entities`].
+#[derive(Debug)]
+pub struct Archetype {
+    // ソート済み
+    types: Vec<TypeId>,
+    entities: Vec<Entity>,
+}
+
+impl Archetype {
+    /// Check if the entities in the archetype have the table component `T`.
+    pub fn contains<T: Component>(&self) -> bool {
+        self.contains_id(TypeId::of::<T>())
+    }
+
+    pub(crate) fn contains_id(&self, type_id: TypeId) -> bool {
+        self.types.binary_search(&type_id).is_ok()
+    }
+
+    pub(crate) fn types(&self) -> &[TypeId] {
+        &self.types
+    }
+
+    /// Returns the entities in the archetype, in the order of the rows.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub const fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+/// Every archetype in a world and the location of every entity
+#[derive(Debug)]
+pub struct Archetypes {
+    archetypes: Vec<Archetype>,
+    ids: HashMap<Vec<TypeId>, usize>,
+    // エンティティのインデックスから位置を引く。生存していないエンティティは None
+    locations: Vec<Option<EntityLocation>>,
+}
+
+impl Archetypes {
+    /// Archetype of the entities which have no table components
+    pub(crate) const EMPTY: usize = 0;
+
+    pub(crate) fn new() -> Self {
+        let mut ids = HashMap::new();
+        ids.insert(Vec::new(), Self::EMPTY);
+        Self {
+            archetypes: vec![Archetype {
+                types: Vec::new(),
+                entities: Vec::new(),
+            }],
+            ids,
+            locations: Vec::new(),
+        }
+    }
+
+    pub(crate) const fn len(&self) -> usize {
+        self.archetypes.len()
+    }
+
+    pub(crate) fn get(&self, archetype: usize) -> &Archetype {
+        &self.archetypes[archetype]
+    }
+
+    #[cfg(feature = "parallel")]
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (usize, &Archetype)> {
+        self.archetypes.iter().enumerate()
+    }
+
+    pub(crate) fn location(&self, index: usize) -> Option<EntityLocation> {
+        self.locations.get(index).copied().flatten()
+    }
+
+    /// Returns the upper bound of the indices of the alive entities.
+    pub(crate) const fn index_len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Returns the archetype which has the types of `archetype` and `type_id`.
+    pub(crate) fn with(&mut self, archetype: usize, type_id: TypeId) -> usize {
+        self.with_all(archetype, [type_id])
+    }
+
+    /// Returns the archetype which has the types of `archetype` and `type_ids`, without creating
+    /// the archetypes in between.
+    pub(crate) fn with_all(
+        &mut self,
+        archetype: usize,
+        type_ids: impl IntoIterator<Item = TypeId>,
+    ) -> usize {
+        let mut types = self.archetypes[archetype].types.clone();
+        for type_id in type_ids {
+            if let Err(position) = types.binary_search(&type_id) {
+                types.insert(position, type_id);
+            }
+        }
+        self.get_or_insert(types)
+    }
+
+    /// Returns the archetype which has the types of `archetype` except `type_id`.
+    pub(crate) fn without(&mut self, archetype: usize, type_id: TypeId) -> usize {
+        self.without_all(archetype, &[type_id])
+    }
+
+    /// Returns the archetype which has the types of `archetype` except `type_ids`, without
+    /// creating the archetypes in between.
+    pub(crate) fn without_all(&mut self, archetype: usize, type_ids: &[TypeId]) -> usize {
+        let mut types = self.archetypes[archetype].types.clone();
+        types.retain(|type_id| !type_ids.contains(type_id));
+        self.get_or_insert(types)
+    }
+
+    fn get_or_insert(&mut self, types: Vec<TypeId>) -> usize {
+        if let Some(&id) = self.ids.get(&types) {
+            return id;
+        }
+        let id = self.archetypes.len();
+        self.archetypes.push(Archetype {
+            types: types.clone(),
+            entities: Vec::new(),
+        });
+        self.ids.insert(types, id);
+        id
+    }
+
+    /// Reserve capacity for `additional` entities in the archetype whose indices are less than
+    /// `index_len`.
+    pub(crate) fn reserve(&mut self, archetype: usize, index_len: usize, additional: usize) {
+        self.locations
+            .reserve(index_len.saturating_sub(self.locations.len()));
+        self.archetypes[archetype].entities.reserve(additional);
+    }
+
+    /// Add a new entity to the end of the archetype.
+    pub(crate) fn push(&mut self, entity: Entity, archetype: usize) -> EntityLocation {
+        let entities = &mut self.archetypes[archetype].entities;
+        let location = EntityLocation {
+            index: entity.index,
+            generation: entity.generation,
+            archetype,
+            row: entities.len(),
+        };
+        entities.push(entity);
+        if self.locations.len() <= entity.index {
+            self.locations.resize(entity.index + 1, None);
+        }
+        self.locations[entity.index] = Some(location);
+        location
+    }
+
+    /// Remove the entity at the location by swapping it with the last entity of the archetype.
+    ///
+    /// The table components must be removed from the same row in the same way.
+    pub(crate) fn swap_remove(&mut self, location: EntityLocation) -> Entity {
+        let entities = &mut self.archetypes[location.archetype].entities;
+        let entity = entities.swap_remove(location.row);
+        if let Some(moved) = entities.get(location.row) {
+            self.locations[moved.index] = Some(EntityLocation {
+                index: moved.index,
+                generation: moved.generation,
+                ..location
+            });
+        }
+        self.locations[location.index] = None;
+        entity
+    }
+
+    /// Move the entity at the location to the end of another archetype.
+    pub(crate) fn move_entity(
+        &mut self,
+        location: EntityLocation,
+        archetype: usize,
+    ) -> EntityLocation {
+        let entity = self.swap_remove(location);
+        self.push(entity, archetype)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::collections::GenerationalVec;
+
+    #[test]
+    fn move_entity() {
+        let mut entities = GenerationalVec::new();
+        let mut archetypes = Archetypes::new();
+        let a = entities.add(());
+        let b = entities.add(());
+        archetypes.push(a, Archetypes::EMPTY);
+        archetypes.push(b, Archetypes::EMPTY);
+
+        let with_i32 = archetypes.with(Archetypes::EMPTY, TypeId::of::<i32>());
+        let with_both = archetypes.with(with_i32, TypeId::of::<f32>());
+        assert_eq!(archetypes.with(with_both, TypeId::of::<i32>()), with_both);
+        assert_eq!(archetypes.without(with_both, TypeId::of::<f32>()), with_i32);
+        let types = [TypeId::of::<f32>(), TypeId::of::<i32>()];
+        assert_eq!(archetypes.with_all(Archetypes::EMPTY, types), with_both);
+        assert_eq!(archetypes.without_all(with_both, &types), Archetypes::EMPTY);
+        assert!(archetypes.get(with_both).contains::<i32>());
+        assert!(!archetypes.get(with_i32).contains::<f32>());
+
+        let location = archetypes.location(a.index).unwrap();
+        let moved = archetypes.move_entity(location, with_i32);
+        assert_eq!(moved.row, 0);
+        assert_eq!(archetypes.location(a.index), Some(moved));
+        // b は a のあった行に移る
+        assert_eq!(archetypes.location(b.index).unwrap().row, 0);
+        assert_eq!(archetypes.location(b.index).unwrap().entity(), b);
+        assert_eq!(archetypes.get(Archetypes::EMPTY).entities(), [b]);
+
+        archetypes.swap_remove(moved);
+        assert_eq!(archetypes.location(a.index), None);
+        assert!(archetypes.get(with_i32).is_empty());
+    }
+
+    #[test]
+    fn spawn_into_target_archetype() {
+        use crate::ecs::{StorageKind, World};
+
+        let mut world = World::builder()
+            .register_component_with_storage::<i32>(StorageKind::Table)
+            .register_component_with_storage::<f32>(StorageKind::Table)
+            .register_component_with_storage::<u8>(StorageKind::Table)
+            .register_component::<u64>()
+            .build();
+        world.spawn((1i32, 1u64, 1.0f32, 1u8));
+        world.spawn_batch((0..100).map(|i| (i, 0u64, 0.0f32, 0u8)));
+        world.spawn_batch((0..100).map(|i| (0u8, i)));
+        // {i32}, {i32, f32} などの途中のアーキタイプは作られない
+        assert_eq!(world.archetypes.len(), 3);
+        // 空のアーキタイプには一度も追加されていない
+        assert_eq!(
+            world.archetypes.archetypes[Archetypes::EMPTY]
+                .entities
+                .capacity(),
+            0
+        );
+        assert_eq!(world.archetypes.get(1).len(), 101);
+        assert_eq!(world.archetypes.get(2).len(), 100);
+    }
+}
@@ -0,0 +1,399 @@
+use std::any::{type_name, TypeId};
+
+use super::{
+    archetype::EntityLocation,
+    change::Tick,
+    removal::Removals,
+    storage::{Components, Storage, Ticked},
+    Component, Entity, MaybeSendSync,
+};
+
+/// Represents a set of components which are attached to or detached from an entity at once.
+///
+/// This trait is implemented for tuples of up to 16 components, and can be derived for structs
+/// whose fields are components.
+///
+/// ```
+/// use xanadu::ecs::{Bundle, World};
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// #[derive(Bundle)]
+/// struct Movable {
+///     position: Position,
+///     velocity: Velocity,
+/// }
+///
+/// let mut world = World::builder()
+///     .register_component::<Position>()
+///     .register_component::<Velocity>()
+///     .build();
+/// let entity = world.spawn(Movable {
+///     position: Position(0.0),
+///     velocity: Velocity(1.0),
+/// });
+/// assert!(world.has_component::<Velocity>(entity));
+/// let movable = world.remove_bundle::<Movable>(entity).unwrap();
+/// assert_eq!(movable.velocity.0, 1.0);
+/// ```
+///
+/// A bundle must not contain the same component type more than once. Worlds reject such bundles
+/// with [`Error::DuplicateComponent`](crate::Error::DuplicateComponent), and the derive macro
+/// rejects the fields of the same type at compile time:
+///
+/// ```compile_fail
+/// use xanadu::ecs::Bundle;
+///
+/// #[derive(Bundle)]
+/// struct Twice {
+///     first: u32,
+///     second: u32,
+/// }
+/// ```
+pub trait Bundle: MaybeSendSync + Sized + 'static {
+    /// Push the type IDs and names of the components in the bundle.
+    fn component_types(types: &mut Vec<(TypeId, &'static str)>);
+
+    /// Write the components to the entity through the writer.
+    fn write(self, writer: &mut BundleWriter<'_>);
+
+    /// Take the components from the entity through the reader.
+    ///
+    /// ## Returns
+    ///
+    /// Returns None if some of the components were not attached. The other components must be
+    /// taken anyway.
+    fn take(reader: &mut BundleReader<'_>) -> Option<Self>;
+}
+
+/// Writes the components of a bundle to an entity, which is passed to [`Bundle::write`]
+///
+/// The entity has already been moved to the archetype which has every table component of the
+/// bundle, so writing the components never moves it again.
+pub struct BundleWriter<'a> {
+    components: &'a mut Components,
+    entity: Entity,
+    location: EntityLocation,
+    tick: Tick,
+}
+
+impl<'a> BundleWriter<'a> {
+    pub(crate) const fn new(
+        components: &'a mut Components,
+        entity: Entity,
+        location: EntityLocation,
+        tick: Tick,
+    ) -> Self {
+        Self {
+            components,
+            entity,
+            location,
+            tick,
+        }
+    }
+
+    /// Attach the component to the entity, replacing the one already attached.
+    pub fn write<T: Component>(&mut self, component: T) {
+        // 登録されていることは World が確認している
+        let Ok(storage) = self.components.get_mut::<T>() else {
+            return;
+        };
+        if let Some(old) = storage.get_ticked_mut(self.location) {
+            old.ticks.changed = self.tick;
+            old.value = component;
+            return;
+        }
+        let component = Ticked::new(component, self.tick);
+        match storage {
+            Storage::SparseVec(vec) => {
+                // エンティティは生存しているので、古い世代として拒否されることはない
+                let inserted = vec.insert(self.entity, component);
+                debug_assert!(inserted.is_ok());
+            }
+            Storage::SparseSet(set) => {
+                set.insert(self.entity, component);
+            }
+            // 他の列は移動済みなので、末尾に追加すれば同じ行になる
+            Storage::Table(table) => table.push(self.location.archetype, component),
+        }
+    }
+}
+
+/// Takes the components of a bundle from an entity, which is passed to [`Bundle::take`]
+///
+/// The table components are removed from the row of the entity, and the entity is moved to the
+/// archetype without them once every component has been taken.
+pub struct BundleReader<'a> {
+    components: &'a mut Components,
+    removals: &'a mut Removals,
+    entity: Entity,
+    location: EntityLocation,
+    tick: Tick,
+}
+
+impl<'a> BundleReader<'a> {
+    pub(crate) const fn new(
+        components: &'a mut Components,
+        removals: &'a mut Removals,
+        entity: Entity,
+        location: EntityLocation,
+        tick: Tick,
+    ) -> Self {
+        Self {
+            components,
+            removals,
+            entity,
+            location,
+            tick,
+        }
+    }
+
+    /// Detach the component from the entity.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the component, or None if it was not attached.
+    pub fn take<T: Component>(&mut self) -> Option<T> {
+        let component = match self.components.get_mut::<T>().ok()? {
+            Storage::SparseVec(vec) => vec.remove(self.entity),
+            Storage::SparseSet(set) => set.remove(self.entity.index),
+            Storage::Table(table) => {
+                table.get(self.location)?;
+                Some(table.swap_remove(self.location))
+            }
+        }?;
+        self.removals
+            .record(TypeId::of::<T>(), self.entity, self.tick);
+        Some(component.value)
+    }
+}
+
+macro_rules! impl_bundle_for_tuple {
+    ($($name:ident),*) => {
+        // 空のタプルでは引数を使わない
+        #[allow(non_snake_case, unused_variables)]
+        impl<$($name: Component),*> Bundle for ($($name,)*) {
+            fn component_types(types: &mut Vec<(TypeId, &'static str)>) {
+                $(types.push((TypeId::of::<$name>(), type_name::<$name>()));)*
+            }
+
+            fn write(self, writer: &mut BundleWriter<'_>) {
+                let ($($name,)*) = self;
+                $(writer.write($name);)*
+            }
+
+            fn take(reader: &mut BundleReader<'_>) -> Option<Self> {
+                $(let $name = reader.take::<$name>();)*
+                Some(($($name?,)*))
+            }
+        }
+    };
+}
+
+impl_bundle_for_tuple!();
+all_tuples!(
+    impl_bundle_for_tuple,
+    P0,
+    P1,
+    P2,
+    P3,
+    P4,
+    P5,
+    P6,
+    P7,
+    P8,
+    P9,
+    P10,
+    P11,
+    P12,
+    P13,
+    P14,
+    P15
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ecs::{Commands, StorageKind, World},
+        Error,
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+
+    #[derive(Debug, PartialEq, crate::ecs::Bundle)]
+    struct Movable {
+        position: Position,
+        velocity: Velocity,
+    }
+
+    #[derive(Debug, PartialEq, crate::ecs::Bundle)]
+    struct Pair<T: Component>(T, Velocity);
+
+    fn world() -> World {
+        World::builder()
+            .register_component::<Position>()
+            .register_component_with_storage::<Velocity>(StorageKind::Table)
+            .register_component::<u32>()
+            .build()
+    }
+
+    #[test]
+    fn tuples() {
+        let mut world = world();
+        let entity = world.spawn((Position(1), Velocity(2)));
+        assert_eq!(
+            world.get_component::<Velocity>(entity).as_deref(),
+            Some(&Velocity(2))
+        );
+        world.insert_bundle(entity, (Velocity(3), 4u32));
+        assert_eq!(
+            world.get_component::<Velocity>(entity).as_deref(),
+            Some(&Velocity(3))
+        );
+        assert_eq!(
+            world.remove_bundle::<(u32, Position)>(entity),
+            Some((4, Position(1)))
+        );
+        // 一部しか付いていなければ None を返すが、付いているものは取り除く
+        assert_eq!(world.remove_bundle::<(Velocity, u32)>(entity), None);
+        assert!(!world.has_component::<Velocity>(entity));
+        let empty = world.spawn(());
+        assert!(world.is_alive(empty));
+    }
+
+    #[test]
+    fn derived() {
+        let mut world = world();
+        let entity = world.spawn(Movable {
+            position: Position(1),
+            velocity: Velocity(2),
+        });
+        world.insert_bundle(entity, Pair(5u32, Velocity(3)));
+        assert_eq!(
+            world.remove_bundle::<Movable>(entity),
+            Some(Movable {
+                position: Position(1),
+                velocity: Velocity(3),
+            })
+        );
+        assert_eq!(world.remove_bundle::<Pair<u32>>(entity), None);
+        assert!(!world.has_component::<u32>(entity));
+    }
+
+    #[test]
+    fn errors_change_nothing() {
+        let mut world = world();
+        assert_eq!(
+            world.try_spawn((Position(1), 1.0f32)),
+            Err(Error::UnregisteredComponent("f32"))
+        );
+        assert_eq!(world.entities.len(), 0);
+
+        let entity = world.spawn((Position(1),));
+        assert!(world
+            .try_insert_bundle(entity, (Velocity(1), 1.0f32))
+            .is_err());
+        assert!(!world.has_component::<Velocity>(entity));
+        assert!(world.try_remove_bundle::<(Position, f32)>(entity).is_err());
+        assert!(world.has_component::<Position>(entity));
+        world.despawn(entity);
+        assert_eq!(
+            world.try_insert_bundle(entity, (Velocity(1),)),
+            Err(Error::DeadEntity(entity))
+        );
+    }
+
+    #[test]
+    fn duplicate_types() {
+        let mut world = world();
+        assert_eq!(
+            world.try_spawn((1u32, Position(1), 2u32)),
+            Err(Error::DuplicateComponent("u32"))
+        );
+        assert_eq!(world.entities.len(), 0);
+        let entity = world.new_entity();
+        assert_eq!(
+            world.try_insert_bundle(entity, Pair(Velocity(1), Velocity(2))),
+            Err(Error::DuplicateComponent(type_name::<Velocity>()))
+        );
+        assert_eq!(
+            world.try_remove_bundle::<(Position, Position)>(entity),
+            Err(Error::DuplicateComponent(type_name::<Position>()))
+        );
+        assert!(!world.has_component::<Velocity>(entity));
+    }
+
+    #[test]
+    fn move_entity_once() {
+        let mut world = World::builder()
+            .register_component_with_storage::<Velocity>(StorageKind::Table)
+            .register_component_with_storage::<i64>(StorageKind::Table)
+            .register_component::<Position>()
+            .build();
+        let a = world.new_entity();
+        let b = world.new_entity();
+        world.insert_bundle(a, (Velocity(1), Position(1), 1i64));
+        world.insert_bundle(b, (2i64, Velocity(2)));
+        // Velocity だけ、または i64 だけのアーキタイプは作られない
+        assert_eq!(world.archetypes.len(), 2);
+
+        assert_eq!(
+            world.remove_bundle::<(i64, Velocity)>(a),
+            Some((1, Velocity(1)))
+        );
+        assert_eq!(world.archetypes.len(), 2);
+        assert!(world.has_component::<Position>(a));
+        // a の行が取り除かれても、b のコンポーネントは同じ行に揃っている
+        assert_eq!(world.get_component::<i64>(b).as_deref(), Some(&2));
+        assert_eq!(
+            world.get_component::<Velocity>(b).as_deref(),
+            Some(&Velocity(2))
+        );
+    }
+
+    #[test]
+    fn commands() {
+        let mut world = world();
+        let entity = world.new_entity();
+        world.execute(|mut commands: Commands<'_>| {
+            commands
+                .entity(entity)
+                .insert_bundle((Position(1), Velocity(1), 1u32))
+                .remove_bundle::<(Position, u32)>();
+        });
+        assert!(!world.has_component::<Position>(entity));
+        assert!(world.has_component::<Velocity>(entity));
+    }
+
+    #[test]
+    fn spawn_batch() {
+        let mut world = world();
+        let first = world.spawn((Position(-1),));
+        world.despawn(first);
+        let entities = world.spawn_batch((0..100).map(|i| (Position(i), Velocity(i * 2))));
+        assert_eq!(entities.len(), 100);
+        // 空いていたスロットから使う
+        assert_eq!(entities[0].index, first.index);
+        for (i, &entity) in (0..).zip(&entities) {
+            assert_eq!(
+                world.get_component::<Position>(entity).as_deref(),
+                Some(&Position(i))
+            );
+            assert_eq!(
+                world.get_component::<Velocity>(entity).as_deref(),
+                Some(&Velocity(i * 2))
+            );
+        }
+        assert_eq!(world.spawn_batch(Vec::<(u32,)>::new()), []);
+        assert_eq!(
+            world.try_spawn_batch(vec![(1u32, 1.0f32)]),
+            Err(Error::UnregisteredComponent("f32"))
+        );
+        assert_eq!(world.entities.len(), 100);
+    }
+}
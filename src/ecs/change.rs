@@ -0,0 +1,113 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+/// Point in time of a world, which is advanced every time a system runs
+///
+/// Components remember the tick when they were attached and when they were last mutated, so that
+/// a system can find the components which were attached or mutated since its last run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Tick(pub(crate) u64);
+
+impl Tick {
+    /// Check if this tick is after `last_run`.
+    pub const fn is_newer_than(self, last_run: Self) -> bool {
+        self.0 > last_run.0
+    }
+}
+
+/// Ticks of a run of a system
+///
+/// This is passed to [`FromWorld::try_from_world`](crate::ecs::FromWorld::try_from_world).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SystemTicks {
+    /// The tick of the previous run of the system, or the default tick if it has never run
+    pub last_run: Tick,
+    /// The tick of the current run of the system
+    pub this_run: Tick,
+}
+
+/// Ticks when a component was attached and last mutated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub(crate) added: Tick,
+    pub(crate) changed: Tick,
+}
+
+impl ComponentTicks {
+    pub(crate) const fn new(tick: Tick) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
+    }
+}
+
+/// Mutable reference to a component, which marks the component changed when it is dereferenced
+/// mutably
+///
+/// This is yielded by queries for `&mut T` and returned by
+/// [`World::get_component_mut`](crate::ecs::World::get_component_mut).
+pub struct Mut<'a, T> {
+    pub(crate) value: &'a mut T,
+    pub(crate) ticks: &'a mut ComponentTicks,
+    pub(crate) this_run: Tick,
+}
+
+impl<'a, T> Mut<'a, T> {
+    /// Returns the mutable reference, marking the component changed.
+    pub const fn into_inner(self) -> &'a mut T {
+        self.ticks.changed = self.this_run;
+        self.value
+    }
+
+    /// Returns the mutable reference without marking the component changed.
+    pub const fn bypass_change_detection(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T> Deref for Mut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Mut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.ticks.changed = self.this_run;
+        self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Mut").field(&*self.value).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mark_changed_on_deref_mut() {
+        let mut value = 1;
+        let mut ticks = ComponentTicks::new(Tick(1));
+        let mut wrapper = Mut {
+            value: &mut value,
+            ticks: &mut ticks,
+            this_run: Tick(3),
+        };
+        assert_eq!(*wrapper, 1);
+        *wrapper.bypass_change_detection() += 1;
+        assert_eq!(wrapper.ticks.changed, Tick(1));
+        *wrapper += 1;
+        assert_eq!(wrapper.ticks.changed, Tick(3));
+        assert_eq!(value, 3);
+        assert_eq!(ticks.added, Tick(1));
+    }
+}
@@ -0,0 +1,411 @@
+use std::{fmt, sync::PoisonError};
+
+use super::{
+    Access, Bundle, Component, Entity, Event, FromWorld, MaybeSend, Relation, Resource,
+    SystemTicks, Traversal, World,
+};
+use crate::Error;
+
+#[cfg(feature = "parallel")]
+type Command = Box<dyn FnOnce(&mut World) + Send>;
+#[cfg(not(feature = "parallel"))]
+type Command = Box<dyn FnOnce(&mut World)>;
+#[cfg(feature = "parallel")]
+type EntityCommand = Box<dyn FnOnce(&mut World, Entity) + Send>;
+#[cfg(not(feature = "parallel"))]
+type EntityCommand = Box<dyn FnOnce(&mut World, Entity)>;
+
+/// Queue of structural changes which are applied to a world later
+#[derive(Default)]
+pub struct CommandQueue {
+    commands: Vec<Command>,
+}
+
+impl CommandQueue {
+    pub(crate) fn push(&mut self, command: impl FnOnce(&mut World) + MaybeSend + 'static) {
+        self.commands.push(Box::new(command));
+    }
+
+    pub(crate) fn append(&mut self, other: &mut Self) {
+        self.commands.append(&mut other.commands);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Apply the commands in the order they were recorded.
+    pub(crate) fn apply(self, world: &mut World) {
+        for command in self.commands {
+            command(world);
+        }
+    }
+}
+
+impl fmt::Debug for CommandQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandQueue")
+            .field("len", &self.commands.len())
+            .finish()
+    }
+}
+
+/// System parameter which records structural changes to the world
+///
+/// Systems cannot spawn or despawn entities or attach or detach components directly, because they
+/// only borrow the component storages. Instead, they record such changes with `Commands`, and the
+/// changes are applied in the order they were recorded when the system finishes, or when
+/// [`World::flush`] is called.
+///
+/// Commands are applied with the panicking APIs of [`World`], e.g. attaching a component to an
+/// entity which has been despawned by an earlier command panics.
+///
+/// ```
+/// use xanadu::ecs::{Commands, Entity, Query, World};
+///
+/// struct Weapon;
+/// struct Bullet;
+///
+/// fn fire_system(mut commands: Commands<'_>, weapons: Query<'_, (Entity, &Weapon)>) {
+///     for _ in weapons.iter() {
+///         commands.spawn().attach(Bullet);
+///     }
+/// }
+///
+/// let mut world = World::builder()
+///     .register_component::<Weapon>()
+///     .register_component::<Bullet>()
+///     .build();
+/// let weapon = world.new_entity();
+/// world.attach_component(weapon, Weapon);
+/// world.execute(fire_system);
+/// world.execute(|bullets: Query<'_, &Bullet>| assert_eq!(bullets.iter().count(), 1));
+/// ```
+pub struct Commands<'world> {
+    queue: CommandQueue,
+    world: &'world World,
+}
+
+impl<'world> Commands<'world> {
+    pub(crate) fn new(world: &'world World) -> Self {
+        Self {
+            queue: CommandQueue::default(),
+            world,
+        }
+    }
+
+    /// Spawn a new entity, and return [`EntityCommands`] to attach components to it.
+    ///
+    /// The ID of the entity is reserved by [`World::reserve_entity`], so it can be taken by
+    /// [`EntityCommands::id`] and referenced by other commands right away.
+    pub fn spawn(&mut self) -> EntityCommands<'_, 'world> {
+        let entity = self.world.reserve_entity();
+        self.entity(entity)
+    }
+
+    /// Return [`EntityCommands`] to modify an existing entity.
+    pub fn entity(&mut self, entity: Entity) -> EntityCommands<'_, 'world> {
+        EntityCommands {
+            commands: self,
+            entity,
+            ops: Vec::new(),
+        }
+    }
+
+    /// Despawn the entity. See [`World::despawn`].
+    pub fn despawn(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.despawn(entity);
+        });
+    }
+
+    /// Despawn the entity and all of its descendants. See [`World::despawn_recursive`].
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.despawn_recursive(entity);
+        });
+    }
+
+    /// Insert the resource. See [`World::insert_resource`].
+    pub fn insert_resource<T: Resource>(&mut self, resource: T) {
+        self.add(move |world| {
+            world.insert_resource(resource);
+        });
+    }
+
+    /// Remove the resource. See [`World::remove_resource`].
+    pub fn remove_resource<T: Resource>(&mut self) {
+        self.add(|world| {
+            world.remove_resource::<T>();
+        });
+    }
+
+    /// Trigger the event for the entity. See [`World::trigger`].
+    pub fn trigger<E: Event>(&mut self, event: E, entity: Entity) {
+        self.add(move |world| {
+            world.trigger(event, entity);
+        });
+    }
+
+    /// Trigger the event for the entity and propagate it. See [`World::trigger_propagating`].
+    pub fn trigger_propagating<T: Traversal, E: Event>(&mut self, event: E, entity: Entity) {
+        self.add(move |world| {
+            world.trigger_propagating::<T, E>(event, entity);
+        });
+    }
+
+    /// Record a custom command, which is called with the world when the commands are applied.
+    pub fn add(&mut self, command: impl FnOnce(&mut World) + MaybeSend + 'static) {
+        self.queue.push(command);
+    }
+}
+
+impl Drop for Commands<'_> {
+    fn drop(&mut self) {
+        self.world
+            .command_queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .append(&mut self.queue);
+    }
+}
+
+impl fmt::Debug for Commands<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Commands")
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+impl FromWorld for Commands<'_> {
+    type Item<'world> = Commands<'world>;
+
+    fn access(_access: &mut Access) {}
+
+    fn try_from_world(world: &World, _ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        Ok(Commands::new(world))
+    }
+}
+
+/// Records changes to a single entity
+///
+/// Returned by [`Commands::spawn`] and [`Commands::entity`]. The changes are recorded as a single
+/// command when this is dropped.
+pub struct EntityCommands<'a, 'world> {
+    commands: &'a mut Commands<'world>,
+    entity: Entity,
+    ops: Vec<EntityCommand>,
+}
+
+impl EntityCommands<'_, '_> {
+    /// Returns the entity, which may not be alive yet if it was spawned by [`Commands::spawn`].
+    pub const fn id(&self) -> Entity {
+        self.entity
+    }
+
+    /// Attach a component to the entity. See [`World::attach_component`].
+    pub fn attach<T: Component>(&mut self, component: T) -> &mut Self {
+        self.ops.push(Box::new(move |world, entity| {
+            world.attach_component(entity, component);
+        }));
+        self
+    }
+
+    /// Detach a component from the entity. See [`World::detach_component`].
+    pub fn detach<T: Component>(&mut self) -> &mut Self {
+        self.ops.push(Box::new(|world, entity| {
+            world.detach_component::<T>(entity);
+        }));
+        self
+    }
+
+    /// Make `parent` the parent of the entity. See [`World::set_parent`].
+    pub fn set_parent(&mut self, parent: Entity) -> &mut Self {
+        self.ops.push(Box::new(move |world, entity| {
+            world.set_parent(entity, parent);
+        }));
+        self
+    }
+
+    /// Make the entity a root. See [`World::remove_parent`].
+    pub fn remove_parent(&mut self) -> &mut Self {
+        self.ops.push(Box::new(|world, entity| {
+            world.remove_parent(entity);
+        }));
+        self
+    }
+
+    /// Attach the components in the bundle to the entity. See [`World::insert_bundle`].
+    pub fn insert_bundle<B: Bundle>(&mut self, bundle: B) -> &mut Self {
+        self.ops.push(Box::new(move |world, entity| {
+            world.insert_bundle(entity, bundle);
+        }));
+        self
+    }
+
+    /// Detach the components in the bundle from the entity. See [`World::remove_bundle`].
+    pub fn remove_bundle<B: Bundle>(&mut self) -> &mut Self {
+        self.ops.push(Box::new(|world, entity| {
+            world.remove_bundle::<B>(entity);
+        }));
+        self
+    }
+
+    /// Relate the entity to `target` with the relation type `R`. See [`World::relate`].
+    pub fn relate<R: Relation>(&mut self, target: Entity) -> &mut Self {
+        self.ops.push(Box::new(move |world, entity| {
+            world.relate::<R>(entity, target);
+        }));
+        self
+    }
+
+    /// Remove the pair of the entity and `target` of the relation type `R`. See
+    /// [`World::unrelate`].
+    pub fn unrelate<R: Relation>(&mut self, target: Entity) -> &mut Self {
+        self.ops.push(Box::new(move |world, entity| {
+            world.unrelate::<R>(entity, target);
+        }));
+        self
+    }
+
+    /// Despawn the entity after the changes recorded so far are applied.
+    pub fn despawn(&mut self) {
+        self.ops.push(Box::new(|world, entity| {
+            world.despawn(entity);
+        }));
+    }
+
+    /// Despawn the entity and all of its descendants after the changes recorded so far are
+    /// applied.
+    pub fn despawn_recursive(&mut self) {
+        self.ops.push(Box::new(|world, entity| {
+            world.despawn_recursive(entity);
+        }));
+    }
+}
+
+impl Drop for EntityCommands<'_, '_> {
+    fn drop(&mut self) {
+        let entity = self.entity;
+        let ops = std::mem::take(&mut self.ops);
+        self.commands.add(move |world| {
+            for op in ops {
+                op(world, entity);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecs::{Query, Res};
+
+    #[test]
+    fn spawn() {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<f32>()
+            .build();
+        world.execute(|mut commands: Commands<'_>| {
+            commands.spawn().attach(1).attach(2.0f32);
+            commands.spawn().attach(3);
+        });
+        world.execute(|query: Query<'_, (&i32, Option<&f32>)>| {
+            let items: Vec<_> = query.iter().map(|(i, f)| (*i, f.copied())).collect();
+            assert_eq!(items, vec![(1, Some(2.0)), (3, None)]);
+        });
+    }
+
+    #[test]
+    fn reference_spawned_entities() {
+        let mut world = World::builder().register_component::<Entity>().build();
+        world.execute(|mut commands: Commands<'_>| {
+            let parent = commands.spawn().id();
+            commands.spawn().attach(parent).set_parent(parent);
+        });
+        let mut pairs = Vec::new();
+        world.execute(|query: Query<'_, (Entity, &Entity)>| {
+            pairs.extend(query.iter().map(|(child, &parent)| (child, parent)));
+        });
+        let [(child, parent)] = pairs[..] else {
+            panic!("{:?}", pairs);
+        };
+        assert!(world.is_alive(parent));
+        assert_eq!(world.parent(child), Some(parent));
+        assert_eq!(world.children(parent), [child]);
+    }
+
+    #[test]
+    fn modify_entity() {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<f32>()
+            .build();
+        let a = world.new_entity();
+        let b = world.new_entity();
+        world.attach_component(a, 1);
+        world.attach_component(b, 2);
+        world.execute(
+            |mut commands: Commands<'_>, query: Query<'_, (Entity, &i32)>| {
+                for (entity, value) in query.iter() {
+                    if *value == 1 {
+                        commands.entity(entity).detach::<i32>().attach(1.0f32);
+                    } else {
+                        commands.despawn(entity);
+                    }
+                }
+            },
+        );
+        assert!(!world.has_component::<i32>(a));
+        assert_eq!(world.get_component::<f32>(a).as_deref(), Some(&1.0));
+        assert!(!world.is_alive(b));
+    }
+
+    #[test]
+    fn resources_and_custom_commands() {
+        let mut world = World::builder().build();
+        world.execute(|mut commands: Commands<'_>| {
+            commands.insert_resource(1i32);
+            commands.add(|world| *world.get_resource_mut::<i32>().unwrap() += 1);
+        });
+        world.execute(|value: Res<'_, i32>| assert_eq!(*value, 2));
+        world.execute(|mut commands: Commands<'_>| commands.remove_resource::<i32>());
+        assert!(!world.has_resource::<i32>());
+    }
+
+    #[test]
+    fn commands_are_applied_in_order() {
+        let mut world = World::builder().build();
+        world.execute(|mut commands: Commands<'_>| {
+            commands.insert_resource(1i32);
+            commands.add(|world| *world.get_resource_mut::<i32>().unwrap() += 1);
+            commands.add(|world| *world.get_resource_mut::<i32>().unwrap() *= 10);
+        });
+        assert_eq!(world.get_resource::<i32>().as_deref(), Some(&20));
+    }
+
+    #[test]
+    fn multiple_commands() {
+        let mut world = World::builder().build();
+        world.execute(|mut a: Commands<'_>, mut b: Commands<'_>| {
+            a.insert_resource(1i32);
+            b.insert_resource(1.0f32);
+        });
+        assert!(world.has_resource::<i32>());
+        assert!(world.has_resource::<f32>());
+    }
+
+    #[test]
+    fn flush() {
+        let mut world = World::builder().build();
+        {
+            let mut commands = Commands::new(&world);
+            commands.insert_resource(1i32);
+        }
+        assert!(!world.has_resource::<i32>());
+        world.flush();
+        assert!(world.has_resource::<i32>());
+    }
+}
@@ -1,4 +1,47 @@
 /// Represents a component that can be attached to an entity.
-pub trait Component: 'static {}
+///
+/// With the `parallel` feature, components must be `Send` and `Sync`, because systems which access
+/// them may be executed on other threads. See [`MaybeSendSync`].
+pub trait Component: MaybeSendSync + 'static {}
 
-impl<T> Component for T where T: 'static {}
+impl<T> Component for T where T: MaybeSendSync + 'static {}
+
+/// Bound of the values stored in a world, which is `Send + Sync` with the `parallel` feature
+///
+/// Without the feature, every type implements this, so components and resources such as `Rc` or
+/// `Cell` can be used.
+#[cfg(feature = "parallel")]
+pub trait MaybeSendSync: Send + Sync {}
+
+#[cfg(feature = "parallel")]
+impl<T: ?Sized + Send + Sync> MaybeSendSync for T {}
+
+/// Bound of the values stored in a world, which is `Send + Sync` with the `parallel` feature
+///
+/// Without the feature, every type implements this, so components and resources such as `Rc` or
+/// `Cell` can be used.
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSendSync {}
+
+#[cfg(not(feature = "parallel"))]
+impl<T: ?Sized> MaybeSendSync for T {}
+
+/// Bound of the values sent to a world, such as commands, which is `Send` with the `parallel`
+/// feature
+///
+/// Without the feature, every type implements this.
+#[cfg(feature = "parallel")]
+pub trait MaybeSend: Send {}
+
+#[cfg(feature = "parallel")]
+impl<T: ?Sized + Send> MaybeSend for T {}
+
+/// Bound of the values sent to a world, such as commands, which is `Send` with the `parallel`
+/// feature
+///
+/// Without the feature, every type implements this.
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSend {}
+
+#[cfg(not(feature = "parallel"))]
+impl<T: ?Sized> MaybeSend for T {}
@@ -0,0 +1,7 @@
+use crate::collections::GenerationalId;
+
+/// ID of an entity
+///
+/// Entities are just [`GenerationalId`]s. A despawned entity never shares its ID with another
+/// entity, because a reused slot has a different generation.
+pub type Entity = GenerationalId;
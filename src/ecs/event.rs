@@ -0,0 +1,260 @@
+use std::fmt;
+
+use super::{Access, FromWorld, MaybeSendSync, SystemTicks, Tick, World};
+use crate::{
+    collections::{AtomicRef, AtomicRefMut},
+    Error,
+};
+
+/// Represents a message sent from a system to other systems, such as a collision or a key press.
+///
+/// Event types must be added to a world by
+/// [`WorldBuilder::add_event`](crate::ecs::WorldBuilder::add_event) before they are sent.
+///
+/// With the `parallel` feature, events must be `Send` and `Sync` like resources.
+pub trait Event: MaybeSendSync + 'static {}
+
+impl<T> Event for T where T: MaybeSendSync + 'static {}
+
+/// Queue of the events of a type, stored in a world as a resource
+///
+/// Events are double-buffered. [`Events::update`] drops the events sent before the previous
+/// update, so every event survives one full run of a [`Schedule`](crate::ecs::Schedule), which
+/// calls [`World::update_events`] at the end of each run.
+pub struct Events<E: Event> {
+    // 前回の update より前に送られたイベント
+    previous: Vec<(E, Tick)>,
+    current: Vec<(E, Tick)>,
+}
+
+impl<E: Event> Events<E> {
+    pub const fn new() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+        }
+    }
+
+    /// Send an event at the tick.
+    pub fn send(&mut self, event: E, tick: Tick) {
+        self.current.push((event, tick));
+    }
+
+    /// Drop the events sent before the previous update.
+    pub fn update(&mut self) {
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    /// Returns an iterator over the events in the order they were sent.
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.iter_with_ticks().map(|(event, _)| event)
+    }
+
+    fn iter_with_ticks(&self) -> impl Iterator<Item = &(E, Tick)> {
+        self.previous.iter().chain(&self.current)
+    }
+
+    /// Returns the number of the events kept in the queue.
+    pub const fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    /// Check if the queue has no events.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<E: Event> Default for Events<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: Event + fmt::Debug> fmt::Debug for Events<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// System parameter which sends events of the type `E`
+///
+/// The system fails to execute if the event type has not been added to the world.
+pub struct EventWriter<'world, E: Event> {
+    events: AtomicRefMut<'world, Events<E>>,
+    this_run: Tick,
+}
+
+impl<E: Event> EventWriter<'_, E> {
+    /// Send an event.
+    pub fn send(&mut self, event: E) {
+        self.events.send(event, self.this_run);
+    }
+
+    /// Send every event in the iterator.
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        for event in events {
+            self.send(event);
+        }
+    }
+}
+
+impl<E: Event> FromWorld for EventWriter<'_, E> {
+    type Item<'world> = EventWriter<'world, E>;
+
+    fn access(access: &mut Access) {
+        access.add_resource_write::<Events<E>>();
+    }
+
+    fn try_from_world(world: &World, ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        Ok(EventWriter {
+            events: world.resources.borrow_mut::<Events<E>>()?,
+            this_run: ticks.this_run,
+        })
+    }
+}
+
+/// System parameter which reads the events of the type `E` sent since the last run of the system
+///
+/// Each system has its own cursor, so every reader sees each event once, as long as it runs in
+/// every run of the schedule. A system executed by [`World::execute`] sees every event kept in the
+/// queue.
+///
+/// ```
+/// use xanadu::ecs::{EventReader, EventWriter, Schedule, World};
+///
+/// struct Collision(u32);
+///
+/// let mut world = World::builder().add_event::<Collision>().build();
+/// let mut schedule = Schedule::new();
+/// schedule.add_system(|mut writer: EventWriter<'_, Collision>| writer.send(Collision(1)));
+/// schedule.add_system(|reader: EventReader<'_, Collision>| {
+///     for collision in reader.iter() {
+///         println!("collision {}", collision.0);
+///     }
+/// });
+/// schedule.run(&mut world);
+/// ```
+pub struct EventReader<'world, E: Event> {
+    events: AtomicRef<'world, Events<E>>,
+    last_run: Tick,
+}
+
+impl<E: Event> EventReader<'_, E> {
+    /// Returns an iterator over the events sent since the last run, in the order they were sent.
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        let last_run = self.last_run;
+        self.events
+            .iter_with_ticks()
+            .filter(move |(_, tick)| tick.is_newer_than(last_run))
+            .map(|(event, _)| event)
+    }
+
+    /// Returns the number of the events sent since the last run.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Check if no events were sent since the last run.
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+impl<E: Event> FromWorld for EventReader<'_, E> {
+    type Item<'world> = EventReader<'world, E>;
+
+    fn access(access: &mut Access) {
+        access.add_resource_read::<Events<E>>();
+    }
+
+    fn try_from_world(world: &World, ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        Ok(EventReader {
+            events: world.resources.borrow::<Events<E>>()?,
+            last_run: ticks.last_run,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecs::{ResMut, Schedule};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Ping(u32);
+
+    #[derive(Default)]
+    struct Seen(Vec<Vec<u32>>);
+
+    fn read(reader: &EventReader<'_, Ping>) -> Vec<u32> {
+        reader.iter().map(|ping| ping.0).collect()
+    }
+
+    fn read_before(reader: EventReader<'_, Ping>, mut seen: ResMut<'_, Seen>) {
+        seen.0[0] = read(&reader);
+    }
+
+    fn read_after(reader: EventReader<'_, Ping>, mut seen: ResMut<'_, Seen>) {
+        seen.0[1] = read(&reader);
+    }
+
+    #[test]
+    fn readers_see_each_event_once() {
+        let mut world = World::builder().add_event::<Ping>().build();
+        world.insert_resource(Seen(vec![Vec::new(); 2]));
+        let mut schedule = Schedule::new();
+        // 送信するシステムの前後で読む
+        schedule.add_system(read_before);
+        schedule.add_system(
+            |mut writer: EventWriter<'_, Ping>, mut count: ResMut<'_, u32>| {
+                *count += 1;
+                if *count <= 2 {
+                    writer.send_batch([Ping(*count * 10), Ping(*count * 10 + 1)]);
+                }
+            },
+        );
+        schedule.add_system(read_after);
+        world.insert_resource(0u32);
+        let mut run = |world: &mut World| {
+            schedule.run(world);
+            world.get_resource::<Seen>().unwrap().0.clone()
+        };
+
+        world.send_event(Ping(1));
+        assert_eq!(run(&mut world), [vec![1], vec![1, 10, 11]]);
+        assert_eq!(run(&mut world), [vec![10, 11], vec![20, 21]]);
+        assert_eq!(run(&mut world), [vec![20, 21], vec![]]);
+        assert_eq!(run(&mut world), [vec![], vec![]]);
+    }
+
+    #[test]
+    fn double_buffer() {
+        let mut events = Events::new();
+        events.send(Ping(1), Tick(1));
+        events.update();
+        events.send(Ping(2), Tick(2));
+        assert_eq!(
+            events.iter().cloned().collect::<Vec<_>>(),
+            [Ping(1), Ping(2)]
+        );
+        events.update();
+        assert_eq!(events.iter().cloned().collect::<Vec<_>>(), [Ping(2)]);
+        events.update();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn unregistered_event() {
+        let mut world = World::builder().build();
+        assert_eq!(
+            world.try_execute(|_: EventReader<'_, Ping>| {}),
+            Err(Error::MissingResource(std::any::type_name::<Events<Ping>>()))
+        );
+        assert_eq!(
+            world.try_send_event(Ping(0)),
+            Err(Error::MissingResource(std::any::type_name::<Events<Ping>>()))
+        );
+    }
+}
@@ -0,0 +1,582 @@
+use std::{any::TypeId, marker::PhantomData};
+
+use super::{
+    archetype::EntityLocation,
+    change::ComponentTicks,
+    storage::{Storage, StoragePtr, Ticked},
+    Access, Archetype, Component, Entity, QueryData, SystemTicks, Tick, World,
+};
+use crate::{collections::AtomicRef, Error};
+
+/// Represents a condition which entities must satisfy to be yielded by a
+/// [`Query`](crate::ecs::Query).
+///
+/// This trait is implemented for [`With<T>`], [`Without<T>`], [`Added<T>`], [`Changed<T>`],
+/// [`Or<T>`], and for tuples of up to 12 filters, which match when all of the filters match. `()`
+/// matches every entity.
+pub trait QueryFilter {
+    /// Borrows of the component storages held while the query is alive
+    type State<'world>;
+
+    /// Declare the components which are read.
+    fn access(access: &mut Access);
+
+    /// Borrow the component storages from the world for a run of a system.
+    ///
+    /// This is called after [`QueryData::borrow`] with the state of the data of the query. If the
+    /// data writes a component which the filter reads, e.g. `Query<&mut T, Changed<T>>`, the
+    /// filter reads it through [`QueryData::write_ptr`] instead of borrowing the storage again.
+    fn borrow<'world, Q: QueryData>(
+        world: &'world World,
+        ticks: SystemTicks,
+        data: &Q::State<'world>,
+    ) -> Result<Self::State<'world>, Error>;
+
+    /// Returns the upper bound of the entity indices that may match, or None if any entity may
+    /// match.
+    fn len(state: &Self::State<'_>) -> Option<usize>;
+
+    /// Returns the entities which may match if a sparse-set component is required, or None if
+    /// any entity may match.
+    fn packed_entities<'state>(state: &'state Self::State<'_>) -> Option<&'state [Entity]>;
+
+    /// Returns true if only the entities in the archetypes accepted by
+    /// [`QueryFilter::matches_archetype`] may match.
+    fn is_dense(state: &Self::State<'_>) -> bool;
+
+    /// Check if the entities in the archetype may match.
+    fn matches_archetype(state: &Self::State<'_>, archetype: &Archetype) -> bool;
+
+    /// Check if the entity at the location matches the filter.
+    fn matches(state: &Self::State<'_>, location: EntityLocation) -> bool;
+}
+
+/// Filter which matches entities that have the component `T`, without fetching it
+pub struct With<T>(PhantomData<T>);
+
+/// Filter which matches entities that do not have the component `T`
+pub struct Without<T>(PhantomData<T>);
+
+/// Filter which matches entities whose component `T` was attached since the last run of the
+/// system
+pub struct Added<T>(PhantomData<T>);
+
+/// Filter which matches entities whose component `T` was attached or mutated since the last run of
+/// the system
+///
+/// A component is regarded as mutated when it is dereferenced mutably through
+/// [`Mut`](crate::ecs::Mut), even if the value is not actually modified.
+pub struct Changed<T>(PhantomData<T>);
+
+/// Filter which matches entities that match any of the filters in the tuple `T`
+///
+/// e.g. `Or<(With<A>, With<B>)>` matches entities which have `A` or `B`.
+pub struct Or<T>(PhantomData<T>);
+
+/// Borrow of a component storage read by a filter
+///
+/// The filter borrows the storage immutably while the query is alive. If the data of the same
+/// query borrows it mutably, e.g. `Query<&mut T, Changed<T>>`, the filter reads whether the
+/// component is attached and its ticks through the pointers taken from that mutable borrow
+/// instead.
+pub struct FilterState<'world, T> {
+    // データが同じストレージを可変で借用している場合は None で、その借用から取り出したポインタを使う
+    _borrow: Option<AtomicRef<'world, Storage<T>>>,
+    ptr: StoragePtr<Ticked<T>>,
+}
+
+impl<'world, T: Component> FilterState<'world, T> {
+    fn new<Q: QueryData>(world: &'world World, data: &Q::State<'world>) -> Result<Self, Error> {
+        let write_ptr = Q::write_ptr(data, TypeId::of::<T>())
+            .and_then(|ptr| ptr.downcast_ref::<StoragePtr<Ticked<T>>>());
+        if let Some(ptr) = write_ptr {
+            return Ok(Self {
+                _borrow: None,
+                ptr: ptr.clone(),
+            });
+        }
+        let borrow = world.components.borrow::<T>()?;
+        let ptr = borrow.as_ptr();
+        Ok(Self {
+            _borrow: Some(borrow),
+            ptr,
+        })
+    }
+
+    fn ticks(&self, location: EntityLocation) -> Option<ComponentTicks> {
+        // SAFETY:
+        // 共有借用か、データの可変借用を持っている間はストレージが変更されない。
+        // フィルターはエンティティを取り出す前に調べるので、同じコンポーネントへの可変参照はまだ存在しない
+        unsafe { self.ptr.get(location) }.map(|ticked| ticked.ticks)
+    }
+
+    fn contains(&self, location: EntityLocation) -> bool {
+        self.ticks(location).is_some()
+    }
+}
+
+impl<T: Component> QueryFilter for With<T> {
+    type State<'world> = FilterState<'world, T>;
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn borrow<'world, Q: QueryData>(
+        world: &'world World,
+        _ticks: SystemTicks,
+        data: &Q::State<'world>,
+    ) -> Result<Self::State<'world>, Error> {
+        FilterState::new::<Q>(world, data)
+    }
+
+    fn len(state: &Self::State<'_>) -> Option<usize> {
+        state.ptr.sparse_len()
+    }
+
+    fn packed_entities<'state>(state: &'state Self::State<'_>) -> Option<&'state [Entity]> {
+        state.ptr.packed_entities()
+    }
+
+    fn is_dense(state: &Self::State<'_>) -> bool {
+        state.ptr.is_table()
+    }
+
+    fn matches_archetype(state: &Self::State<'_>, archetype: &Archetype) -> bool {
+        !state.ptr.is_table() || archetype.contains::<T>()
+    }
+
+    fn matches(state: &Self::State<'_>, location: EntityLocation) -> bool {
+        state.contains(location)
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T> {
+    type State<'world> = FilterState<'world, T>;
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn borrow<'world, Q: QueryData>(
+        world: &'world World,
+        _ticks: SystemTicks,
+        data: &Q::State<'world>,
+    ) -> Result<Self::State<'world>, Error> {
+        FilterState::new::<Q>(world, data)
+    }
+
+    fn len(_state: &Self::State<'_>) -> Option<usize> {
+        None
+    }
+
+    fn packed_entities<'state>(_state: &'state Self::State<'_>) -> Option<&'state [Entity]> {
+        None
+    }
+
+    fn is_dense(_state: &Self::State<'_>) -> bool {
+        false
+    }
+
+    fn matches_archetype(state: &Self::State<'_>, archetype: &Archetype) -> bool {
+        !state.ptr.is_table() || !archetype.contains::<T>()
+    }
+
+    fn matches(state: &Self::State<'_>, location: EntityLocation) -> bool {
+        !state.contains(location)
+    }
+}
+
+macro_rules! impl_query_filter_for_tick {
+    ($filter:ident, $tick:ident) => {
+        impl<T: Component> QueryFilter for $filter<T> {
+            // コンポーネントの格納場所と、前回システムを実行したティック
+            type State<'world> = (FilterState<'world, T>, Tick);
+
+            fn access(access: &mut Access) {
+                access.add_read::<T>();
+            }
+
+            fn borrow<'world, Q: QueryData>(
+                world: &'world World,
+                ticks: SystemTicks,
+                data: &Q::State<'world>,
+            ) -> Result<Self::State<'world>, Error> {
+                Ok((FilterState::new::<Q>(world, data)?, ticks.last_run))
+            }
+
+            fn len((state, _): &Self::State<'_>) -> Option<usize> {
+                state.ptr.sparse_len()
+            }
+
+            fn packed_entities<'state>(
+                (state, _): &'state Self::State<'_>,
+            ) -> Option<&'state [Entity]> {
+                state.ptr.packed_entities()
+            }
+
+            fn is_dense((state, _): &Self::State<'_>) -> bool {
+                state.ptr.is_table()
+            }
+
+            fn matches_archetype((state, _): &Self::State<'_>, archetype: &Archetype) -> bool {
+                !state.ptr.is_table() || archetype.contains::<T>()
+            }
+
+            fn matches((state, last_run): &Self::State<'_>, location: EntityLocation) -> bool {
+                state
+                    .ticks(location)
+                    .is_some_and(|ticks| ticks.$tick.is_newer_than(*last_run))
+            }
+        }
+    };
+}
+
+impl_query_filter_for_tick!(Added, added);
+impl_query_filter_for_tick!(Changed, changed);
+
+impl QueryFilter for () {
+    type State<'world> = ();
+
+    fn access(_access: &mut Access) {}
+
+    fn borrow<'world, Q: QueryData>(
+        _world: &'world World,
+        _ticks: SystemTicks,
+        _data: &Q::State<'world>,
+    ) -> Result<Self::State<'world>, Error> {
+        Ok(())
+    }
+
+    fn len(_state: &Self::State<'_>) -> Option<usize> {
+        None
+    }
+
+    fn packed_entities<'state>(_state: &'state Self::State<'_>) -> Option<&'state [Entity]> {
+        None
+    }
+
+    fn is_dense(_state: &Self::State<'_>) -> bool {
+        false
+    }
+
+    fn matches_archetype(_state: &Self::State<'_>, _archetype: &Archetype) -> bool {
+        true
+    }
+
+    fn matches(_state: &Self::State<'_>, _location: EntityLocation) -> bool {
+        true
+    }
+}
+
+macro_rules! impl_query_filter_for_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*) {
+            type State<'world> = ($($name::State<'world>,)*);
+
+            fn access(access: &mut Access) {
+                $($name::access(access);)*
+            }
+
+            fn borrow<'world, Q: QueryData>(
+                world: &'world World,
+                ticks: SystemTicks,
+                data: &Q::State<'world>,
+            ) -> Result<Self::State<'world>, Error> {
+                Ok(($($name::borrow::<Q>(world, ticks, data)?,)*))
+            }
+
+            fn len(state: &Self::State<'_>) -> Option<usize> {
+                let ($($name,)*) = state;
+                let len = None;
+                $(let len = match ($name::len($name), len) {
+                    (Some(a), Some(b)) => Some(usize::min(a, b)),
+                    (a, b) => a.or(b),
+                };)*
+                len
+            }
+
+            fn packed_entities<'state>(state: &'state Self::State<'_>) -> Option<&'state [Entity]> {
+                let ($($name,)*) = state;
+                let entities: Option<&[Entity]> = None;
+                $(let entities = match ($name::packed_entities($name), entities) {
+                    (Some(a), Some(b)) if b.len() < a.len() => Some(b),
+                    (a, b) => a.or(b),
+                };)*
+                entities
+            }
+
+            fn is_dense(state: &Self::State<'_>) -> bool {
+                let ($($name,)*) = state;
+                $($name::is_dense($name))||*
+            }
+
+            fn matches_archetype(state: &Self::State<'_>, archetype: &Archetype) -> bool {
+                let ($($name,)*) = state;
+                $($name::matches_archetype($name, archetype))&&*
+            }
+
+            fn matches(state: &Self::State<'_>, location: EntityLocation) -> bool {
+                let ($($name,)*) = state;
+                $($name::matches($name, location))&&*
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($name: QueryFilter),*> QueryFilter for Or<($($name,)*)> {
+            type State<'world> = ($($name::State<'world>,)*);
+
+            fn access(access: &mut Access) {
+                $($name::access(access);)*
+            }
+
+            fn borrow<'world, Q: QueryData>(
+                world: &'world World,
+                ticks: SystemTicks,
+                data: &Q::State<'world>,
+            ) -> Result<Self::State<'world>, Error> {
+                Ok(($($name::borrow::<Q>(world, ticks, data)?,)*))
+            }
+
+            fn len(state: &Self::State<'_>) -> Option<usize> {
+                let ($($name,)*) = state;
+                let len = Some(0);
+                $(let len = match ($name::len($name), len) {
+                    (Some(a), Some(b)) => Some(usize::max(a, b)),
+                    _ => None,
+                };)*
+                len
+            }
+
+            fn packed_entities<'state>(_state: &'state Self::State<'_>) -> Option<&'state [Entity]> {
+                None
+            }
+
+            fn is_dense(state: &Self::State<'_>) -> bool {
+                let ($($name,)*) = state;
+                $($name::is_dense($name))&&*
+            }
+
+            fn matches_archetype(state: &Self::State<'_>, archetype: &Archetype) -> bool {
+                let ($($name,)*) = state;
+                $($name::matches_archetype($name, archetype))||*
+            }
+
+            fn matches(state: &Self::State<'_>, location: EntityLocation) -> bool {
+                let ($($name,)*) = state;
+                $($name::matches($name, location))||*
+            }
+        }
+    };
+}
+
+all_tuples!(
+    impl_query_filter_for_tuple,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecs::{FromWorld, Query, ResMut, Schedule, StorageKind};
+
+    fn world() -> World {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<u32>()
+            .register_component::<f32>()
+            .build();
+        for i in 0..6i32 {
+            let entity = world.new_entity();
+            world.attach_component(entity, i);
+            if i % 2 == 0 {
+                world.attach_component(entity, i as u32);
+            }
+            if i % 3 == 0 {
+                world.attach_component(entity, i as f32);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn with() {
+        let mut world = world();
+        world.execute(|query: Query<'_, &i32, With<u32>>| {
+            assert_eq!(query.iter().copied().collect::<Vec<_>>(), [0, 2, 4]);
+        });
+    }
+
+    #[test]
+    fn without() {
+        let mut world = world();
+        world.execute(|query: Query<'_, &i32, Without<u32>>| {
+            assert_eq!(query.iter().copied().collect::<Vec<_>>(), [1, 3, 5]);
+        });
+    }
+
+    #[test]
+    fn tuple() {
+        let mut world = world();
+        world.execute(|query: Query<'_, &i32, (With<f32>, Without<u32>)>| {
+            assert_eq!(query.iter().copied().collect::<Vec<_>>(), [3]);
+        });
+    }
+
+    type WithUOrF = Or<(With<u32>, With<f32>)>;
+    type WithoutIOrWithF = Or<(Without<i32>, With<f32>)>;
+
+    #[test]
+    fn or() {
+        let mut world = world();
+        world.execute(|query: Query<'_, &i32, WithUOrF>| {
+            assert_eq!(query.iter().copied().collect::<Vec<_>>(), [0, 2, 3, 4]);
+        });
+    }
+
+    #[test]
+    fn or_without() {
+        let mut world = world();
+        world.execute(|query: Query<'_, Option<&u32>, WithoutIOrWithF>| {
+            assert_eq!(query.iter().collect::<Vec<_>>(), [Some(&0), None]);
+        });
+    }
+
+    fn increment_changed(mut query: Query<'_, &mut u32, Changed<u32>>) {
+        for mut value in query.iter_mut() {
+            *value += 1;
+        }
+    }
+
+    fn values(world: &mut World) -> Vec<u32> {
+        let mut values = Vec::new();
+        world.execute(|query: Query<'_, &u32>| values.extend(query.iter().copied()));
+        values
+    }
+
+    #[test]
+    fn filter_holds_borrow() {
+        let world = world();
+        let ticks = SystemTicks::default();
+        let changed = Query::<&i32, Changed<u32>>::try_from_world(&world, ticks).unwrap();
+        assert_eq!(
+            Query::<&mut u32>::try_from_world(&world, ticks).err(),
+            Some(Error::BorrowConflict("u32"))
+        );
+        drop(changed);
+        assert!(Query::<&mut u32, Changed<u32>>::try_from_world(&world, ticks).is_ok());
+    }
+
+    #[test]
+    fn filter_mutably_borrowed_component() {
+        let mut world = world();
+        world.execute(
+            |mut query: Query<'_, &mut u32, (With<u32>, Without<f32>)>| {
+                for mut value in query.iter_mut() {
+                    *value *= 10;
+                }
+            },
+        );
+        assert_eq!(values(&mut world), [0, 20, 40]);
+
+        // システム自身による変更は、次の実行では変更として扱われない
+        let mut schedule = Schedule::new();
+        schedule.add_system(increment_changed);
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(values(&mut world), [1, 21, 41]);
+
+        world.execute(|mut query: Query<'_, (Entity, &mut u32), Added<u32>>| {
+            let (_, mut value) = query.iter_mut().next().unwrap();
+            *value = 100;
+        });
+        schedule.run(&mut world);
+        assert_eq!(values(&mut world), [101, 21, 41]);
+    }
+
+    #[derive(Default)]
+    struct Seen {
+        added: Vec<i32>,
+        changed: Vec<i32>,
+    }
+
+    fn record(
+        added: Query<'_, &i32, Added<i32>>,
+        changed: Query<'_, &i32, Changed<i32>>,
+        mut seen: ResMut<'_, Seen>,
+    ) {
+        seen.added = added.iter().copied().collect();
+        seen.added.sort();
+        seen.changed = changed.iter().copied().collect();
+        seen.changed.sort();
+    }
+
+    fn change_detection(kind: StorageKind) {
+        let mut world = World::builder()
+            .register_component_with_storage::<i32>(kind)
+            .build();
+        world.insert_resource(Seen::default());
+        let mut schedule = Schedule::new();
+        schedule.add_system(record);
+        let mut mutate = Schedule::new();
+        mutate.add_system(|mut query: Query<'_, &mut i32>| {
+            for mut value in query.iter_mut() {
+                // 読むだけなら変更扱いにならない
+                if *value % 2 == 0 {
+                    *value += 100;
+                }
+            }
+        });
+        let mut run = |world: &mut World| {
+            schedule.run(world);
+            let seen = world.get_resource::<Seen>().unwrap();
+            (seen.added.clone(), seen.changed.clone())
+        };
+
+        let a = world.new_entity();
+        let b = world.new_entity();
+        world.attach_component(a, 1);
+        world.attach_component(b, 2);
+        assert_eq!(run(&mut world), (vec![1, 2], vec![1, 2]));
+        assert_eq!(run(&mut world), (vec![], vec![]));
+
+        *world.get_component_mut::<i32>(a).unwrap() += 10;
+        let c = world.new_entity();
+        world.attach_component(c, 3);
+        assert_eq!(run(&mut world), (vec![3], vec![3, 11]));
+
+        assert_eq!(world.attach_component(c, 4), Some(3));
+        assert_eq!(run(&mut world), (vec![], vec![4]));
+
+        mutate.run(&mut world);
+        assert_eq!(run(&mut world), (vec![], vec![102, 104]));
+        assert_eq!(run(&mut world), (vec![], vec![]));
+    }
+
+    #[test]
+    fn change_detection_sparse_vec() {
+        change_detection(StorageKind::SparseVec);
+    }
+
+    #[test]
+    fn change_detection_sparse_set() {
+        change_detection(StorageKind::SparseSet);
+    }
+
+    #[test]
+    fn change_detection_table() {
+        change_detection(StorageKind::Table);
+    }
+}
@@ -0,0 +1,340 @@
+use std::ops::Deref;
+
+use super::{
+    archetype::Archetypes, storage::Storage, Access, Commands, Entity, FromWorld, SystemTicks,
+    Traversal, World,
+};
+use crate::{collections::AtomicRef, Error};
+
+/// Component which links an entity to its parent
+///
+/// This is attached by [`World::set_parent`] and detached by [`World::remove_parent`], which keep
+/// it consistent with [`Children`] of the parent. Detaching it directly, e.g. with
+/// [`World::detach_component`], removes the entity from the children of the parent as well, but
+/// don't attach it directly.
+///
+/// Events triggered by `World::trigger_propagating::<Parent, _>` propagate up to the root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parent(pub(crate) Entity);
+
+impl Parent {
+    /// Returns the parent entity.
+    pub const fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+impl Traversal for Parent {
+    fn next(&self) -> Option<Entity> {
+        Some(self.0)
+    }
+}
+
+/// Component which lists the children of an entity in the order they were added
+///
+/// This is maintained by [`World::set_parent`] and [`World::remove_parent`], and is detached when
+/// the last child is removed. Detaching it directly makes the children roots, but don't attach it
+/// directly.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Children(pub(crate) Vec<Entity>);
+
+impl Deref for Children {
+    type Target = [Entity];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// System parameter which reads the hierarchy of entities
+///
+/// It borrows [`Parent`] and [`Children`] immutably, and walks the hierarchy without copying the
+/// lists of children. [`World::hierarchy`] returns it outside of systems.
+///
+/// ```
+/// use xanadu::ecs::{Entity, Hierarchy, World};
+///
+/// let mut world = World::builder().build();
+/// let [root, child, grandchild] = [(); 3].map(|()| world.new_entity());
+/// world.set_parent(child, root);
+/// world.set_parent(grandchild, child);
+///
+/// world.execute(move |hierarchy: Hierarchy<'_>| {
+///     assert_eq!(hierarchy.children(root), [child]);
+///     assert_eq!(hierarchy.ancestors(grandchild).collect::<Vec<_>>(), [child, root]);
+///     assert_eq!(hierarchy.descendants(root).collect::<Vec<_>>(), [child, grandchild]);
+/// });
+/// ```
+pub struct Hierarchy<'world> {
+    archetypes: &'world Archetypes,
+    parents: AtomicRef<'world, Storage<Parent>>,
+    children: AtomicRef<'world, Storage<Children>>,
+}
+
+impl<'world> Hierarchy<'world> {
+    pub(crate) fn new(world: &'world World) -> Result<Self, Error> {
+        Ok(Self {
+            archetypes: &world.archetypes,
+            parents: world.components.borrow::<Parent>()?,
+            children: world.components.borrow::<Children>()?,
+        })
+    }
+
+    /// Returns the parent of the entity, or None if it has no parent or is not alive.
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        let location = self.archetypes.location(entity.index)?;
+        if location.entity() != entity {
+            return None;
+        }
+        self.parents.get(location).map(Parent::get)
+    }
+
+    /// Returns the children of the entity in the order they were added.
+    pub fn children(&self, entity: Entity) -> &[Entity] {
+        let children = self
+            .archetypes
+            .location(entity.index)
+            .filter(|location| location.entity() == entity)
+            .and_then(|location| self.children.get(location));
+        children.map_or(&[], |children| children)
+    }
+
+    /// Returns an iterator over the ancestors of the entity, from its parent to the root.
+    pub fn ancestors(&self, entity: Entity) -> Ancestors<'_> {
+        Ancestors {
+            hierarchy: self,
+            next: self.parent(entity),
+        }
+    }
+
+    /// Returns an iterator over the descendants of the entity in depth-first order.
+    pub fn descendants(&self, entity: Entity) -> Descendants<'_> {
+        Descendants {
+            hierarchy: self,
+            stack: vec![self.children(entity).iter()],
+        }
+    }
+}
+
+impl FromWorld for Hierarchy<'_> {
+    type Item<'world> = Hierarchy<'world>;
+
+    fn access(access: &mut Access) {
+        access.add_read::<Parent>();
+        access.add_read::<Children>();
+    }
+
+    fn try_from_world(world: &World, _ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        Hierarchy::new(world)
+    }
+}
+
+/// Iterator over the ancestors of an entity, from its parent to the root
+///
+/// Returned by [`Hierarchy::ancestors`].
+pub struct Ancestors<'a> {
+    hierarchy: &'a Hierarchy<'a>,
+    next: Option<Entity>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entity = self.next?;
+        self.next = self.hierarchy.parent(entity);
+        Some(entity)
+    }
+}
+
+/// Iterator over the descendants of an entity in depth-first order, where each entity comes before
+/// its children
+///
+/// Returned by [`Hierarchy::descendants`].
+pub struct Descendants<'a> {
+    hierarchy: &'a Hierarchy<'a>,
+    // 訪問中の各階層で、まだ訪問していない兄弟
+    stack: Vec<std::slice::Iter<'a, Entity>>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(&entity) => {
+                    self.stack.push(self.hierarchy.children(entity).iter());
+                    return Some(entity);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Parent が外されるときに、親の Children からも取り除く
+pub fn on_remove_parent(world: &World, child: Entity, commands: &mut Commands<'_>) {
+    if let Some(parent) = world.parent(child) {
+        commands.add(move |world: &mut World| world.remove_child(parent, child));
+    }
+}
+
+/// Children が外されるときに、子の Parent も外す
+pub fn on_remove_children(world: &World, parent: Entity, commands: &mut Commands<'_>) {
+    for child in world.children(parent) {
+        commands.add(move |world: &mut World| {
+            // 子がすでに別の親に付け替えられていれば何もしない
+            if world.parent(child) == Some(parent) {
+                world.detach_component::<Parent>(child);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ecs::{Commands, Query, ResMut, Trigger},
+        Error,
+    };
+
+    // root ─┬─ a ─── c
+    //       └─ b
+    fn tree() -> (World, [Entity; 4]) {
+        let mut world = World::builder().build();
+        let [root, a, b, c] = [(); 4].map(|()| world.new_entity());
+        world.set_parent(a, root);
+        world.set_parent(b, root);
+        world.set_parent(c, a);
+        (world, [root, a, b, c])
+    }
+
+    #[test]
+    fn set_and_remove_parent() {
+        let (mut world, [root, a, b, c]) = tree();
+        assert_eq!(world.parent(a), Some(root));
+        assert_eq!(world.parent(root), None);
+        assert_eq!(world.children(root), [a, b]);
+        assert_eq!(
+            world.hierarchy().ancestors(c).collect::<Vec<_>>(),
+            [a, root]
+        );
+        assert_eq!(
+            world.hierarchy().descendants(root).collect::<Vec<_>>(),
+            [a, c, b]
+        );
+
+        // 親を付け替えると、前の親の Children から取り除かれる
+        world.set_parent(a, b);
+        assert_eq!(world.children(root), [b]);
+        assert_eq!(world.children(b), [a]);
+        assert_eq!(
+            world.hierarchy().ancestors(c).collect::<Vec<_>>(),
+            [a, b, root]
+        );
+
+        assert_eq!(world.remove_parent(a), Some(b));
+        assert_eq!(world.remove_parent(a), None);
+        assert!(!world.has_component::<Children>(b));
+        world.execute(|query: Query<'_, (Entity, &Parent)>| {
+            let mut parents: Vec<_> = query.iter().map(|(e, p)| (e, p.get())).collect();
+            parents.sort_by_key(|(entity, _)| entity.index);
+            assert_eq!(parents, [(b, root), (c, a)]);
+        });
+    }
+
+    #[test]
+    fn detach_directly() {
+        let (mut world, [root, a, b, c]) = tree();
+        assert_eq!(world.detach_component::<Parent>(b), Some(Parent(root)));
+        assert_eq!(world.children(root), [a]);
+
+        // 子を持つエンティティから Children を外すと、子は根になる
+        world.remove_bundle::<(Children,)>(a);
+        assert_eq!(world.parent(c), None);
+        assert_eq!(world.parent(a), Some(root));
+        world.execute(|query: Query<'_, &Parent>| assert_eq!(query.iter().count(), 1));
+    }
+
+    #[test]
+    fn hierarchy_param() {
+        let (mut world, [root, a, b, c]) = tree();
+        let despawned = world.new_entity();
+        world.despawn(despawned);
+        world.execute(move |hierarchy: Hierarchy<'_>| {
+            assert_eq!(hierarchy.parent(c), Some(a));
+            assert_eq!(hierarchy.children(root), [a, b]);
+            assert_eq!(hierarchy.children(b), []);
+            assert_eq!(hierarchy.children(despawned), []);
+            assert_eq!(hierarchy.ancestors(root).next(), None);
+            assert_eq!(hierarchy.descendants(a).collect::<Vec<_>>(), [c]);
+        });
+        // Parent を書き換えるクエリとは同時に使えない
+        assert_eq!(
+            world.try_execute(|_: Hierarchy<'_>, _: Query<'_, &mut Parent>| {}),
+            Err(Error::ConflictingAccess(std::any::type_name::<Parent>()))
+        );
+    }
+
+    #[test]
+    fn cyclic_hierarchy() {
+        let (mut world, [root, a, _, c]) = tree();
+        assert_eq!(
+            world.try_set_parent(root, c),
+            Err(Error::CyclicHierarchy(root))
+        );
+        assert_eq!(world.try_set_parent(a, a), Err(Error::CyclicHierarchy(a)));
+        assert_eq!(world.parent(root), None);
+    }
+
+    #[test]
+    fn despawn() {
+        let (mut world, [root, a, b, c]) = tree();
+        world.despawn(a);
+        assert_eq!(world.children(root), [b]);
+        assert_eq!(world.parent(c), None);
+        assert_eq!(world.try_set_parent(c, a), Err(Error::DeadEntity(a)));
+
+        world.set_parent(c, b);
+        assert!(world.despawn_recursive(root));
+        assert!(!world.is_alive(b));
+        assert!(!world.is_alive(c));
+        assert!(!world.despawn_recursive(root));
+    }
+
+    #[test]
+    fn commands() {
+        let (mut world, [root, a, b, c]) = tree();
+        world.execute(|mut commands: Commands<'_>| {
+            commands.spawn().set_parent(c);
+            commands.entity(b).remove_parent();
+            commands.entity(a).despawn_recursive();
+        });
+        assert_eq!(world.children(root), []);
+        assert!(world.is_alive(b));
+        assert_eq!(world.hierarchy().descendants(root).count(), 0);
+        world.execute(|query: Query<'_, &Parent>| assert_eq!(query.iter().count(), 0));
+    }
+
+    #[test]
+    fn propagate_to_parents() {
+        struct Click;
+
+        let (mut world, [root, a, _, c]) = tree();
+        world.insert_resource(Vec::<Entity>::new());
+        for entity in [root, a, c] {
+            world.observe_entity::<Click, _>(
+                entity,
+                |trigger: Trigger<'_, Click>, mut log: ResMut<'_, Vec<Entity>>| {
+                    log.push(trigger.entity());
+                },
+            );
+        }
+        world.trigger_propagating::<Parent, _>(Click, c);
+        assert_eq!(*world.get_resource::<Vec<Entity>>().unwrap(), [c, a, root]);
+    }
+}
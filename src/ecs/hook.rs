@@ -0,0 +1,280 @@
+use std::{any::TypeId, collections::HashMap, fmt};
+
+use super::{Commands, Entity, World};
+
+/// Callback which is called when a component is attached to, replaced on or removed from an entity
+///
+/// It is called with the world, the entity, and [`Commands`] which are applied after the change
+/// to the world has finished. With the `parallel` feature, it must be `Send` and `Sync`.
+#[cfg(feature = "parallel")]
+pub type Hook = Box<dyn Fn(&World, Entity, &mut Commands<'_>) + Send + Sync>;
+/// Callback which is called when a component is attached to, replaced on or removed from an entity
+///
+/// It is called with the world, the entity, and [`Commands`] which are applied after the change
+/// to the world has finished. With the `parallel` feature, it must be `Send` and `Sync`.
+#[cfg(not(feature = "parallel"))]
+pub type Hook = Box<dyn Fn(&World, Entity, &mut Commands<'_>)>;
+
+/// Kind of change to a component which hooks are called for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    Add,
+    Insert,
+    Replace,
+    Remove,
+}
+
+/// Hooks registered for a component type
+pub struct ComponentHooks {
+    on_add: Vec<Hook>,
+    on_insert: Vec<Hook>,
+    on_replace: Vec<Hook>,
+    on_remove: Vec<Hook>,
+    // despawn のときに型を知らずにコンポーネントの有無を調べるため
+    contains: fn(&World, Entity) -> bool,
+}
+
+impl ComponentHooks {
+    const fn get(&self, kind: HookKind) -> &Vec<Hook> {
+        match kind {
+            HookKind::Add => &self.on_add,
+            HookKind::Insert => &self.on_insert,
+            HookKind::Replace => &self.on_replace,
+            HookKind::Remove => &self.on_remove,
+        }
+    }
+
+    fn get_mut(&mut self, kind: HookKind) -> &mut Vec<Hook> {
+        match kind {
+            HookKind::Add => &mut self.on_add,
+            HookKind::Insert => &mut self.on_insert,
+            HookKind::Replace => &mut self.on_replace,
+            HookKind::Remove => &mut self.on_remove,
+        }
+    }
+}
+
+/// Hooks of every component type in a world
+///
+/// Hooks of different component types are called in the order the types were first given a hook.
+#[derive(Default)]
+pub struct Hooks {
+    map: HashMap<TypeId, ComponentHooks>,
+    // フックを初めて登録した順の型。HashMap の順番は実行ごとに変わるため
+    order: Vec<TypeId>,
+}
+
+impl Hooks {
+    pub(crate) fn add(
+        &mut self,
+        type_id: TypeId,
+        kind: HookKind,
+        contains: fn(&World, Entity) -> bool,
+        hook: Hook,
+    ) {
+        let order = &mut self.order;
+        self.map
+            .entry(type_id)
+            .or_insert_with(|| {
+                order.push(type_id);
+                ComponentHooks {
+                    on_add: Vec::new(),
+                    on_insert: Vec::new(),
+                    on_replace: Vec::new(),
+                    on_remove: Vec::new(),
+                    contains,
+                }
+            })
+            .get_mut(kind)
+            .push(hook);
+    }
+
+    /// Call the hooks of the kind for the component type.
+    ///
+    /// ## Returns
+    ///
+    /// Returns false if no hooks were called.
+    pub(crate) fn call(
+        &self,
+        world: &World,
+        type_id: TypeId,
+        kind: HookKind,
+        entity: Entity,
+    ) -> bool {
+        let Some(hooks) = self.map.get(&type_id) else {
+            return false;
+        };
+        let hooks = hooks.get(kind);
+        if hooks.is_empty() {
+            return false;
+        }
+        let mut commands = Commands::new(world);
+        for hook in hooks {
+            hook(world, entity, &mut commands);
+        }
+        true
+    }
+
+    /// Call the hooks of the kinds for every component type attached to the entity, in the order
+    /// the types were registered.
+    ///
+    /// ## Returns
+    ///
+    /// Returns false if no hooks were called.
+    pub(crate) fn call_attached(&self, world: &World, kinds: &[HookKind], entity: Entity) -> bool {
+        let mut called = false;
+        for &type_id in &self.order {
+            if !(self.map[&type_id].contains)(world, entity) {
+                continue;
+            }
+            for &kind in kinds {
+                called |= self.call(world, type_id, kind, entity);
+            }
+        }
+        called
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use crate::ecs::StorageKind;
+
+    use super::*;
+
+    type Log = Arc<Mutex<Vec<(&'static str, Option<i32>)>>>;
+
+    // フックが呼ばれた順番と、そのときのコンポーネントの値を記録する
+    fn world(kind: StorageKind, log: &Log) -> World {
+        let hook = |name: &'static str| {
+            let log = Arc::clone(log);
+            move |world: &World, entity: Entity, _: &mut Commands<'_>| {
+                let value = world.get_component::<i32>(entity).map(|value| *value);
+                log.lock().unwrap().push((name, value));
+            }
+        };
+        World::builder()
+            .register_component_with_storage::<i32>(kind)
+            .on_add::<i32>(hook("add"))
+            .on_insert::<i32>(hook("insert"))
+            .on_replace::<i32>(hook("replace"))
+            .on_remove::<i32>(hook("remove"))
+            .build()
+    }
+
+    fn lifecycle(kind: StorageKind) {
+        let log = Log::default();
+        let mut world = world(kind, &log);
+        let take = || std::mem::take(&mut *log.lock().unwrap());
+
+        let entity = world.new_entity();
+        world.attach_component(entity, 1);
+        assert_eq!(take(), [("add", Some(1)), ("insert", Some(1))]);
+        world.attach_component(entity, 2);
+        assert_eq!(take(), [("replace", Some(1)), ("insert", Some(2))]);
+        world.detach_component::<i32>(entity);
+        assert_eq!(take(), [("replace", Some(2)), ("remove", Some(2))]);
+        world.detach_component::<i32>(entity);
+        assert_eq!(take(), []);
+
+        world.attach_component(entity, 3);
+        take();
+        world.despawn(entity);
+        assert_eq!(take(), [("replace", Some(3)), ("remove", Some(3))]);
+        let other = world.new_entity();
+        world.despawn(other);
+        assert_eq!(take(), []);
+    }
+
+    #[test]
+    fn lifecycle_sparse_vec() {
+        lifecycle(StorageKind::SparseVec);
+    }
+
+    #[test]
+    fn lifecycle_sparse_set() {
+        lifecycle(StorageKind::SparseSet);
+    }
+
+    #[test]
+    fn lifecycle_table() {
+        lifecycle(StorageKind::Table);
+    }
+
+    #[test]
+    fn deferred_commands() {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<f32>()
+            .on_add::<i32>(|world, entity, commands| {
+                // フックの中ではまだ f32 は付いていない
+                assert!(!world.has_component::<f32>(entity));
+                commands.entity(entity).attach(1.0f32);
+            })
+            .on_remove::<f32>(|_, entity, commands| {
+                commands.entity(entity).detach::<i32>();
+            })
+            .build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 1);
+        assert_eq!(world.get_component::<f32>(entity).as_deref(), Some(&1.0));
+
+        world.execute(|mut commands: Commands<'_>| {
+            commands.entity(entity).detach::<f32>();
+        });
+        assert!(!world.has_component::<i32>(entity));
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    #[test]
+    fn non_thread_safe_hooks() {
+        use std::{cell::Cell, rc::Rc};
+
+        let added = Rc::new(Cell::new(0));
+        let cloned = Rc::clone(&added);
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .on_add::<i32>(move |_, _, _| cloned.set(cloned.get() + 1))
+            .build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 1);
+        world.attach_component(entity, 2);
+        assert_eq!(added.get(), 1);
+    }
+
+    #[test]
+    fn despawn_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let hook = |name: &'static str| {
+            let log = Arc::clone(&log);
+            move |_: &World, _: Entity, _: &mut Commands<'_>| log.lock().unwrap().push(name)
+        };
+        let mut world = World::builder()
+            .register_component::<u8>()
+            .register_component::<u16>()
+            .register_component::<u32>()
+            .register_component::<u64>()
+            .on_remove::<u32>(hook("u32"))
+            .on_remove::<u8>(hook("u8"))
+            .on_replace::<u64>(hook("u64"))
+            .on_remove::<u16>(hook("u16"))
+            .on_replace::<u32>(hook("u32"))
+            .build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 0u8);
+        world.attach_component(entity, 0u16);
+        world.attach_component(entity, 0u32);
+        world.attach_component(entity, 0u64);
+        world.despawn(entity);
+        assert_eq!(*log.lock().unwrap(), ["u32", "u32", "u8", "u64", "u16"]);
+    }
+}
@@ -0,0 +1,327 @@
+use std::{any::TypeId, collections::HashMap, fmt};
+
+use super::{Access, Component, Entity, Event, FromWorld, SystemTicks, Tick, World};
+use crate::{collections::AtomicRefMut, Error};
+
+#[cfg(feature = "parallel")]
+type BoxedObserver = Box<dyn FnMut(&World, Tick) -> Result<Tick, Error> + Send + Sync>;
+#[cfg(not(feature = "parallel"))]
+type BoxedObserver = Box<dyn FnMut(&World, Tick) -> Result<Tick, Error>>;
+
+/// Represents a component which links an entity to the next entity a triggered event propagates
+/// to, such as its parent.
+///
+/// See [`World::trigger_propagating`].
+pub trait Traversal: Component {
+    /// Returns the next entity, or None if the event should not propagate further.
+    fn next(&self) -> Option<Entity>;
+}
+
+struct Observer {
+    // 空なら全てのエンティティを監視する
+    entities: Vec<Entity>,
+    system: BoxedObserver,
+    last_run: Tick,
+}
+
+impl Observer {
+    fn watches(&self, target: Entity, original: bool) -> bool {
+        if self.entities.is_empty() {
+            original
+        } else {
+            self.entities.contains(&target)
+        }
+    }
+}
+
+/// Observers of every event type in a world
+#[derive(Default)]
+pub struct Observers {
+    map: HashMap<TypeId, Vec<Observer>>,
+}
+
+impl Observers {
+    pub(crate) fn add<E: Event>(&mut self, entities: Vec<Entity>, system: BoxedObserver) {
+        self.map
+            .entry(TypeId::of::<E>())
+            .or_default()
+            .push(Observer {
+                entities,
+                system,
+                last_run: Tick::default(),
+            });
+    }
+
+    /// Call the observers of the event type for the entity and then for the entities it
+    /// propagates to.
+    ///
+    /// The observers are taken out of the world while they run, so that they can borrow the world.
+    pub(crate) fn trigger<E: Event>(
+        world: &mut World,
+        event: E,
+        entity: Entity,
+        traverse: Option<fn(&World, Entity) -> Option<Entity>>,
+    ) -> Result<E, Error> {
+        let Some(mut observers) = world.observers.map.remove(&TypeId::of::<E>()) else {
+            return Ok(event);
+        };
+        world.insert_resource(TriggerState {
+            event,
+            entity,
+            propagate: traverse.is_some(),
+        });
+        let result = Self::run::<E>(world, &mut observers, entity, traverse);
+        // 実行中に追加されたオブザーバーは無いので、そのまま戻す
+        world.observers.map.insert(TypeId::of::<E>(), observers);
+        // 直前に insert したので必ず存在する
+        let state = world.remove_resource::<TriggerState<E>>().unwrap();
+        world.flush();
+        result.map(|()| state.event)
+    }
+
+    fn run<E: Event>(
+        world: &mut World,
+        observers: &mut [Observer],
+        mut target: Entity,
+        traverse: Option<fn(&World, Entity) -> Option<Entity>>,
+    ) -> Result<(), Error> {
+        let mut original = true;
+        loop {
+            for observer in observers
+                .iter_mut()
+                .filter(|observer| observer.watches(target, original))
+            {
+                observer.last_run = (observer.system)(world, observer.last_run)?;
+            }
+            original = false;
+            let next = traverse.and_then(|traverse| traverse(world, target));
+            let state = world.get_resource_mut::<TriggerState<E>>().unwrap();
+            match next {
+                Some(next) if state.propagate => {
+                    state.entity = next;
+                    target = next;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observers")
+            .field("len", &self.map.values().map(Vec::len).sum::<usize>())
+            .finish()
+    }
+}
+
+/// Event being triggered, stored as a resource while the observers run
+pub struct TriggerState<E: Event> {
+    event: E,
+    entity: Entity,
+    propagate: bool,
+}
+
+/// System parameter which gives an observer the event being triggered and its target entity
+///
+/// Systems which take this parameter are executed by [`World::trigger`] instead of
+/// [`Schedule`](crate::ecs::Schedule), and fail to execute outside a trigger.
+///
+/// ```
+/// use xanadu::ecs::{Entity, ResMut, Trigger, World};
+///
+/// struct Explode;
+/// struct Health(u32);
+///
+/// let mut world = World::builder().register_component::<Health>().build();
+/// let entity = world.new_entity();
+/// world.attach_component(entity, Health(10));
+/// world.observe_entity::<Explode, _>(
+///     entity,
+///     |trigger: Trigger<'_, Explode>, mut log: ResMut<'_, Vec<Entity>>| {
+///         log.push(trigger.entity());
+///     },
+/// );
+/// world.insert_resource(Vec::<Entity>::new());
+///
+/// world.trigger(Explode, entity);
+/// assert_eq!(*world.get_resource::<Vec<Entity>>().unwrap(), [entity]);
+/// ```
+pub struct Trigger<'world, E: Event> {
+    state: AtomicRefMut<'world, TriggerState<E>>,
+}
+
+impl<E: Event> Trigger<'_, E> {
+    /// Returns the event being triggered.
+    pub fn event(&self) -> &E {
+        &self.state.event
+    }
+
+    /// Returns the event being triggered, which later observers see after the modification.
+    pub fn event_mut(&mut self) -> &mut E {
+        &mut self.state.event
+    }
+
+    /// Returns the entity which the event is triggered for, which is one of the entities it
+    /// propagated to if it was triggered by [`World::trigger_propagating`].
+    pub fn entity(&self) -> Entity {
+        self.state.entity
+    }
+
+    /// Set whether the event propagates to the next entity after the observers of the current
+    /// entity.
+    ///
+    /// Events triggered by [`World::trigger_propagating`] propagate by default, and the others
+    /// never propagate.
+    pub fn propagate(&mut self, propagate: bool) {
+        self.state.propagate = propagate;
+    }
+}
+
+impl<E: Event> FromWorld for Trigger<'_, E> {
+    type Item<'world> = Trigger<'world, E>;
+
+    fn access(access: &mut Access) {
+        access.add_resource_write::<TriggerState<E>>();
+    }
+
+    fn try_from_world(world: &World, _ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        Ok(Trigger {
+            state: world.resources.borrow_mut::<TriggerState<E>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecs::{Commands, Query, ResMut};
+
+    struct Damage(u32);
+
+    struct Up(Entity);
+
+    impl Traversal for Up {
+        fn next(&self) -> Option<Entity> {
+            Some(self.0)
+        }
+    }
+
+    type Log = Vec<(Entity, u32)>;
+
+    fn log(trigger: Trigger<'_, Damage>, mut log: ResMut<'_, Log>) {
+        log.push((trigger.entity(), trigger.event().0));
+    }
+
+    fn world() -> World {
+        let mut world = World::builder()
+            .register_component::<u32>()
+            .register_component::<Up>()
+            .build();
+        world.insert_resource(Log::new());
+        world
+    }
+
+    fn take_log(world: &mut World) -> Log {
+        std::mem::take(world.get_resource_mut::<Log>().unwrap())
+    }
+
+    #[test]
+    fn scoped_observers() {
+        let mut world = world();
+        let a = world.new_entity();
+        let b = world.new_entity();
+        world.observe_entity::<Damage, _>(a, log);
+        world.trigger(Damage(1), a);
+        world.trigger(Damage(2), b);
+        assert_eq!(take_log(&mut world), [(a, 1)]);
+
+        world.observe::<Damage, _>(log);
+        world.trigger(Damage(3), a);
+        world.trigger(Damage(4), b);
+        assert_eq!(take_log(&mut world), [(a, 3), (a, 3), (b, 4)]);
+    }
+
+    #[test]
+    fn propagation() {
+        let mut world = world();
+        let root = world.new_entity();
+        let middle = world.new_entity();
+        let leaf = world.new_entity();
+        world.attach_component(middle, Up(root));
+        world.attach_component(leaf, Up(middle));
+        for entity in [root, middle, leaf] {
+            world.observe_entity::<Damage, _>(entity, log);
+        }
+        world.observe::<Damage, _>(|mut trigger: Trigger<'_, Damage>| {
+            trigger.event_mut().0 += 1;
+        });
+
+        // オブザーバーは追加された順に実行される
+        let event = world.trigger_propagating::<Up, _>(Damage(1), leaf);
+        assert_eq!(event.0, 2);
+        assert_eq!(take_log(&mut world), [(leaf, 1), (middle, 2), (root, 2)]);
+
+        world.trigger(Damage(1), leaf);
+        assert_eq!(take_log(&mut world), [(leaf, 1)]);
+
+        world.observe_entity::<Damage, _>(middle, |mut trigger: Trigger<'_, Damage>| {
+            trigger.propagate(false);
+        });
+        world.trigger_propagating::<Up, _>(Damage(1), leaf);
+        assert_eq!(take_log(&mut world), [(leaf, 1), (middle, 2)]);
+    }
+
+    #[test]
+    fn observers_take_system_parameters() {
+        let mut world = world();
+        let entity = world.new_entity();
+        world.attach_component(entity, 10u32);
+        world.observe::<Damage, _>(
+            |trigger: Trigger<'_, Damage>,
+             mut query: Query<'_, (Entity, &mut u32)>,
+             mut commands: Commands<'_>| {
+                for (entity, mut health) in query.iter_mut() {
+                    if entity == trigger.entity() {
+                        *health = health.saturating_sub(trigger.event().0);
+                        if *health == 0 {
+                            commands.despawn(entity);
+                        }
+                    }
+                }
+            },
+        );
+        world.trigger(Damage(3), entity);
+        assert_eq!(world.get_component::<u32>(entity).as_deref(), Some(&7));
+        world.execute(|mut commands: Commands<'_>| commands.trigger(Damage(7), entity));
+        assert!(!world.is_alive(entity));
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    #[test]
+    fn non_thread_safe_observers() {
+        use std::{cell::Cell, rc::Rc};
+
+        let mut world = world();
+        let entity = world.new_entity();
+        let total = Rc::new(Cell::new(0));
+        let cloned = Rc::clone(&total);
+        world.observe::<Damage, _>(move |trigger: Trigger<'_, Damage>| {
+            cloned.set(cloned.get() + trigger.event().0);
+        });
+        world.trigger(Damage(3), entity);
+        world.trigger(Damage(4), entity);
+        assert_eq!(total.get(), 7);
+    }
+
+    #[test]
+    fn trigger_outside_observers() {
+        let mut world = world();
+        assert_eq!(
+            world.try_execute(|_: Trigger<'_, Damage>| {}),
+            Err(Error::MissingResource(std::any::type_name::<
+                TriggerState<Damage>,
+            >()))
+        );
+    }
+}
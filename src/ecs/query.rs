@@ -0,0 +1,1131 @@
+use std::{
+    any::{Any, TypeId},
+    ops::Range,
+};
+
+use super::{
+    archetype::{Archetypes, EntityLocation},
+    storage::{Storage, StoragePtr, Ticked},
+    Access, Archetype, Component, Entity, FromWorld, Mut, QueryFilter, SystemTicks, Tick, World,
+};
+use crate::{
+    collections::{AtomicRef, AtomicRefMut, GenerationalVec},
+    Error,
+};
+
+/// Represents a type which can be fetched from each entity by a [`Query`].
+///
+/// This trait is implemented for `&T` and `&mut T` where `T` is a [`Component`], for [`Entity`]
+/// which yields the ID of the entity itself, for `Option<Q>`
+/// which fetches `Q` if present without filtering out entities, and for tuples of up to 12 such
+/// types. `&mut T` yields [`Mut<T>`](Mut), which marks the component changed when it is
+/// dereferenced mutably.
+///
+/// # Safety
+///
+/// [`QueryData::borrow`] must hold borrows of every component storage which
+/// [`QueryData::fetch`] reads or writes, so that the references returned by `fetch` never alias a
+/// mutable reference handed out by another borrow. The pointers returned by
+/// [`QueryData::write_ptr`] must stay valid while the state is alive.
+pub unsafe trait QueryData {
+    /// Borrows of the component storages held while the query is alive
+    type State<'world>;
+    /// The type yielded for each entity
+    type Item<'query>;
+
+    /// Declare the components which are read or written.
+    fn access(access: &mut Access);
+
+    /// Borrow the component storages from the world for a run of a system.
+    fn borrow(world: &World, ticks: SystemTicks) -> Result<Self::State<'_>, Error>;
+
+    /// Returns the upper bound of the entity indices that may match, or None if any entity may
+    /// match.
+    fn len(state: &Self::State<'_>) -> Option<usize>;
+
+    /// Returns the entities which may match if a sparse-set component is required, or None if
+    /// any entity may match.
+    fn packed_entities<'state>(state: &'state Self::State<'_>) -> Option<&'state [Entity]>;
+
+    /// Returns true if only the entities in the archetypes accepted by
+    /// [`QueryData::matches_archetype`] may match, i.e. a table component is required.
+    fn is_dense(state: &Self::State<'_>) -> bool;
+
+    /// Check if the entities in the archetype may match.
+    fn matches_archetype(state: &Self::State<'_>, archetype: &Archetype) -> bool;
+
+    /// Returns the pointers to the components of the type which the state borrows mutably, so that
+    /// the filter of the same query can read them without borrowing the storage again.
+    ///
+    /// The returned value is a `StoragePtr<Ticked<T>>` for the component type `T` of `type_id`.
+    fn write_ptr<'state>(
+        _state: &'state Self::State<'_>,
+        _type_id: TypeId,
+    ) -> Option<&'state dyn Any> {
+        None
+    }
+
+    /// Fetch the item of the entity at the location, or return None if the entity does not match.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not fetch the same entity again while a previously fetched item is alive
+    /// unless `Self` is [`ReadOnlyQueryData`].
+    unsafe fn fetch<'query>(
+        state: &'query Self::State<'_>,
+        location: EntityLocation,
+    ) -> Option<Self::Item<'query>>;
+}
+
+/// Represents a [`QueryData`] which only reads components.
+///
+/// # Safety
+///
+/// [`QueryData::fetch`] must not return mutable references.
+pub unsafe trait ReadOnlyQueryData: QueryData {}
+
+unsafe impl<T: Component> QueryData for &T {
+    type State<'world> = AtomicRef<'world, Storage<T>>;
+    type Item<'query> = &'query T;
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn borrow(world: &World, _ticks: SystemTicks) -> Result<Self::State<'_>, Error> {
+        world.components.borrow::<T>()
+    }
+
+    fn len(state: &Self::State<'_>) -> Option<usize> {
+        state.sparse_len()
+    }
+
+    fn packed_entities<'state>(state: &'state Self::State<'_>) -> Option<&'state [Entity]> {
+        state.packed_entities()
+    }
+
+    fn is_dense(state: &Self::State<'_>) -> bool {
+        state.is_table()
+    }
+
+    fn matches_archetype(state: &Self::State<'_>, archetype: &Archetype) -> bool {
+        state.matches_archetype(archetype)
+    }
+
+    unsafe fn fetch<'query>(
+        state: &'query Self::State<'_>,
+        location: EntityLocation,
+    ) -> Option<Self::Item<'query>> {
+        state.get(location)
+    }
+}
+
+unsafe impl<T: Component> ReadOnlyQueryData for &T {}
+
+/// Mutable borrow of a component storage held by a [`Query`]
+pub struct WriteState<'world, T> {
+    _borrow: AtomicRefMut<'world, Storage<T>>,
+    // 借用を取った時点で要素へのポインタを取り出しておき、以降は _borrow に触らない
+    ptr: StoragePtr<Ticked<T>>,
+    this_run: Tick,
+}
+
+unsafe impl<T: Component> QueryData for &mut T {
+    type State<'world> = WriteState<'world, T>;
+    type Item<'query> = Mut<'query, T>;
+
+    fn access(access: &mut Access) {
+        access.add_write::<T>();
+    }
+
+    fn borrow(world: &World, ticks: SystemTicks) -> Result<Self::State<'_>, Error> {
+        let mut borrow = world.components.borrow_mut::<T>()?;
+        let ptr = borrow.as_mut_ptr();
+        Ok(WriteState {
+            _borrow: borrow,
+            ptr,
+            this_run: ticks.this_run,
+        })
+    }
+
+    fn len(state: &Self::State<'_>) -> Option<usize> {
+        state.ptr.sparse_len()
+    }
+
+    fn packed_entities<'state>(state: &'state Self::State<'_>) -> Option<&'state [Entity]> {
+        state.ptr.packed_entities()
+    }
+
+    fn is_dense(state: &Self::State<'_>) -> bool {
+        state.ptr.is_table()
+    }
+
+    fn matches_archetype(state: &Self::State<'_>, archetype: &Archetype) -> bool {
+        !state.ptr.is_table() || archetype.contains::<T>()
+    }
+
+    fn write_ptr<'state>(
+        state: &'state Self::State<'_>,
+        type_id: TypeId,
+    ) -> Option<&'state dyn Any> {
+        if type_id == TypeId::of::<T>() {
+            Some(&state.ptr)
+        } else {
+            None
+        }
+    }
+
+    unsafe fn fetch<'query>(
+        state: &'query Self::State<'_>,
+        location: EntityLocation,
+    ) -> Option<Self::Item<'query>> {
+        // SAFETY:
+        // 借用を持っている間はストレージが変更されない。また、同じエンティティの要素を同時に複数回取り出さないことは
+        // 呼び出し側が保証するので、可変参照がエイリアスすることはない
+        let ticked = unsafe { state.ptr.get_mut(location) }?;
+        Some(ticked.as_mut(state.this_run))
+    }
+}
+
+unsafe impl QueryData for Entity {
+    type State<'world> = &'world GenerationalVec<()>;
+    type Item<'query> = Self;
+
+    fn access(_access: &mut Access) {}
+
+    fn borrow(world: &World, _ticks: SystemTicks) -> Result<Self::State<'_>, Error> {
+        Ok(&world.entities)
+    }
+
+    fn len(_state: &Self::State<'_>) -> Option<usize> {
+        None
+    }
+
+    fn packed_entities<'state>(_state: &'state Self::State<'_>) -> Option<&'state [Entity]> {
+        None
+    }
+
+    fn is_dense(_state: &Self::State<'_>) -> bool {
+        false
+    }
+
+    fn matches_archetype(_state: &Self::State<'_>, _archetype: &Archetype) -> bool {
+        true
+    }
+
+    unsafe fn fetch<'query>(
+        state: &'query Self::State<'_>,
+        location: EntityLocation,
+    ) -> Option<Self::Item<'query>> {
+        state.id_at(location.index)
+    }
+}
+
+unsafe impl ReadOnlyQueryData for Entity {}
+
+unsafe impl<Q: QueryData> QueryData for Option<Q> {
+    type State<'world> = Q::State<'world>;
+    type Item<'query> = Option<Q::Item<'query>>;
+
+    fn access(access: &mut Access) {
+        Q::access(access);
+    }
+
+    fn borrow(world: &World, ticks: SystemTicks) -> Result<Self::State<'_>, Error> {
+        Q::borrow(world, ticks)
+    }
+
+    fn len(_state: &Self::State<'_>) -> Option<usize> {
+        None
+    }
+
+    fn packed_entities<'state>(_state: &'state Self::State<'_>) -> Option<&'state [Entity]> {
+        None
+    }
+
+    fn is_dense(_state: &Self::State<'_>) -> bool {
+        false
+    }
+
+    fn matches_archetype(_state: &Self::State<'_>, _archetype: &Archetype) -> bool {
+        true
+    }
+
+    fn write_ptr<'state>(
+        state: &'state Self::State<'_>,
+        type_id: TypeId,
+    ) -> Option<&'state dyn Any> {
+        Q::write_ptr(state, type_id)
+    }
+
+    unsafe fn fetch<'query>(
+        state: &'query Self::State<'_>,
+        location: EntityLocation,
+    ) -> Option<Self::Item<'query>> {
+        // SAFETY: 呼び出し側の保証をそのまま引き継ぐ
+        Some(unsafe { Q::fetch(state, location) })
+    }
+}
+
+unsafe impl<Q: ReadOnlyQueryData> ReadOnlyQueryData for Option<Q> {}
+
+macro_rules! impl_query_data_for_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case)]
+        unsafe impl<$($name: QueryData),*> QueryData for ($($name,)*) {
+            type State<'world> = ($($name::State<'world>,)*);
+            type Item<'query> = ($($name::Item<'query>,)*);
+
+            fn access(access: &mut Access) {
+                $($name::access(access);)*
+            }
+
+            fn borrow(world: &World, ticks: SystemTicks) -> Result<Self::State<'_>, Error> {
+                Ok(($($name::borrow(world, ticks)?,)*))
+            }
+
+            fn len(state: &Self::State<'_>) -> Option<usize> {
+                let ($($name,)*) = state;
+                let len = None;
+                $(let len = match ($name::len($name), len) {
+                    (Some(a), Some(b)) => Some(usize::min(a, b)),
+                    (a, b) => a.or(b),
+                };)*
+                len
+            }
+
+            fn packed_entities<'state>(state: &'state Self::State<'_>) -> Option<&'state [Entity]> {
+                let ($($name,)*) = state;
+                let entities: Option<&[Entity]> = None;
+                $(let entities = match ($name::packed_entities($name), entities) {
+                    (Some(a), Some(b)) if b.len() < a.len() => Some(b),
+                    (a, b) => a.or(b),
+                };)*
+                entities
+            }
+
+            fn is_dense(state: &Self::State<'_>) -> bool {
+                let ($($name,)*) = state;
+                $($name::is_dense($name))||*
+            }
+
+            fn matches_archetype(state: &Self::State<'_>, archetype: &Archetype) -> bool {
+                let ($($name,)*) = state;
+                $($name::matches_archetype($name, archetype))&&*
+            }
+
+            fn write_ptr<'state>(
+                state: &'state Self::State<'_>,
+                type_id: TypeId,
+            ) -> Option<&'state dyn Any> {
+                let ($($name,)*) = state;
+                None$(.or_else(|| $name::write_ptr($name, type_id)))*
+            }
+
+            unsafe fn fetch<'query>(
+                state: &'query Self::State<'_>,
+                location: EntityLocation,
+            ) -> Option<Self::Item<'query>> {
+                let ($($name,)*) = state;
+                // SAFETY: 呼び出し側の保証をそのまま引き継ぐ
+                Some(($(unsafe { $name::fetch($name, location) }?,)*))
+            }
+        }
+
+        unsafe impl<$($name: ReadOnlyQueryData),*> ReadOnlyQueryData for ($($name,)*) {}
+    };
+}
+
+all_tuples!(
+    impl_query_data_for_tuple,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L
+);
+
+/// System parameter which iterates over the entities that have all of the requested components.
+///
+/// `Q` is a [`QueryData`], e.g. `&Position`, `&mut Position`, `(&mut Position, &Velocity)` or
+/// `(&Position, Option<&Velocity>)`. `F` is a [`QueryFilter`], e.g. `Without<Frozen>`.
+///
+/// If the query requires a component stored in [`StorageKind::Table`](crate::ecs::StorageKind),
+/// it only visits the entities in the archetypes which have it. Otherwise, if it requires a
+/// component stored in [`StorageKind::SparseSet`](crate::ecs::StorageKind), it only visits the
+/// entities which have the rarest of them. Otherwise, it visits the entity indices up to the
+/// shortest storage of the required components.
+///
+/// ```rust
+/// use xanadu::ecs::{Query, World};
+///
+/// struct Position(f64);
+/// struct Velocity(f64);
+///
+/// let mut world = World::builder()
+///     .register_component::<Position>()
+///     .register_component::<Velocity>()
+///     .build();
+/// let entity = world.new_entity();
+/// world.attach_component(entity, Position(0.0));
+/// world.attach_component(entity, Velocity(1.0));
+///
+/// world.execute(|mut query: Query<'_, (&mut Position, &Velocity)>| {
+///     for (mut pos, vel) in query.iter_mut() {
+///         pos.0 += vel.0;
+///     }
+/// });
+/// ```
+pub struct Query<'world, Q: QueryData, F: QueryFilter = ()> {
+    data: Q::State<'world>,
+    filter: F::State<'world>,
+    archetypes: &'world Archetypes,
+}
+
+impl<Q: QueryData, F: QueryFilter> FromWorld for Query<'_, Q, F> {
+    type Item<'world> = Query<'world, Q, F>;
+
+    fn access(access: &mut Access) {
+        Q::access(access);
+        F::access(access);
+    }
+
+    fn try_from_world(world: &World, ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        // データが可変で借用したストレージは、フィルターがそのポインタを使って読む
+        let data = Q::borrow(world, ticks)?;
+        let filter = F::borrow::<Q>(world, ticks, &data)?;
+        Ok(Query {
+            data,
+            filter,
+            archetypes: &world.archetypes,
+        })
+    }
+}
+
+impl<'world, Q: QueryData, F: QueryFilter> Query<'world, Q, F> {
+    /// Returns an iterator over the items of the matching entities.
+    pub fn iter(&self) -> QueryIter<'_, 'world, Q, F>
+    where
+        Q: ReadOnlyQueryData,
+    {
+        QueryIter::new(self)
+    }
+
+    /// Returns an iterator over the items of the matching entities, which may contain mutable
+    /// references.
+    pub fn iter_mut(&mut self) -> QueryIter<'_, 'world, Q, F> {
+        QueryIter::new(self)
+    }
+
+    /// Returns a parallel iterator over the items of the matching entities.
+    #[cfg(feature = "parallel")]
+    pub const fn par_iter(&self) -> QueryParIter<'_, 'world, Q, F>
+    where
+        Q: ReadOnlyQueryData,
+    {
+        QueryParIter {
+            query: self,
+            batch_size: None,
+        }
+    }
+
+    /// Returns a parallel iterator over the items of the matching entities, which may contain
+    /// mutable references.
+    #[cfg(feature = "parallel")]
+    pub const fn par_iter_mut(&mut self) -> QueryParIter<'_, 'world, Q, F> {
+        QueryParIter {
+            query: self,
+            batch_size: None,
+        }
+    }
+
+    fn is_dense(&self) -> bool {
+        Q::is_dense(&self.data) || F::is_dense(&self.filter)
+    }
+
+    fn matches_archetype(&self, archetype: &Archetype) -> bool {
+        Q::matches_archetype(&self.data, archetype) && F::matches_archetype(&self.filter, archetype)
+    }
+
+    /// Returns the shortest list of the entities which may match if a sparse-set component is
+    /// required and the list is shorter than the range of the entity indices.
+    fn packed_entities(&self) -> Option<&[Entity]> {
+        let entities = match (
+            Q::packed_entities(&self.data),
+            F::packed_entities(&self.filter),
+        ) {
+            (Some(a), Some(b)) if b.len() < a.len() => Some(b),
+            (a, b) => a.or(b),
+        };
+        entities.filter(|entities| entities.len() < self.sparse_len())
+    }
+
+    /// Returns the upper bound of the entity indices which may match.
+    fn sparse_len(&self) -> usize {
+        let len = match (Q::len(&self.data), F::len(&self.filter)) {
+            (Some(a), Some(b)) => Some(usize::min(a, b)),
+            (a, b) => a.or(b),
+        };
+        usize::min(len.unwrap_or(usize::MAX), self.archetypes.index_len())
+    }
+}
+
+impl<'query, 'world, Q: ReadOnlyQueryData, F: QueryFilter> IntoIterator
+    for &'query Query<'world, Q, F>
+{
+    type Item = Q::Item<'query>;
+    type IntoIter = QueryIter<'query, 'world, Q, F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'query, 'world, Q: QueryData, F: QueryFilter> IntoIterator
+    for &'query mut Query<'world, Q, F>
+{
+    type Item = Q::Item<'query>;
+    type IntoIter = QueryIter<'query, 'world, Q, F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Iterator over the items of a [`Query`]
+pub struct QueryIter<'query, 'world, Q: QueryData, F: QueryFilter> {
+    query: &'query Query<'world, Q, F>,
+    cursor: Cursor<'query>,
+}
+
+enum Cursor<'query> {
+    // エンティティのインデックス順に調べる
+    Sparse {
+        indices: Range<usize>,
+    },
+    // 疎集合に詰められたエンティティを順に調べる
+    Packed {
+        entities: std::slice::Iter<'query, Entity>,
+    },
+    // 条件に合うアーキタイプの行を順に調べる
+    Dense {
+        archetypes: Range<usize>,
+        archetype: usize,
+        entities: &'query [Entity],
+        rows: Range<usize>,
+    },
+}
+
+impl<'query, 'world, Q: QueryData, F: QueryFilter> QueryIter<'query, 'world, Q, F> {
+    /// Create an iterator over the whole query. Callers must ensure that only one iterator which
+    /// may yield mutable references exists at a time.
+    fn new(query: &'query Query<'world, Q, F>) -> Self {
+        let cursor = if query.is_dense() {
+            Cursor::Dense {
+                archetypes: 0..query.archetypes.len(),
+                archetype: 0,
+                entities: &[],
+                rows: 0..0,
+            }
+        } else if let Some(entities) = query.packed_entities() {
+            Cursor::Packed {
+                entities: entities.iter(),
+            }
+        } else {
+            Cursor::Sparse {
+                indices: 0..query.sparse_len(),
+            }
+        };
+        Self { query, cursor }
+    }
+
+    /// Create an iterator over the entity indices in the range of a sparse query.
+    #[cfg(feature = "parallel")]
+    const fn indices(query: &'query Query<'world, Q, F>, indices: Range<usize>) -> Self {
+        Self {
+            query,
+            cursor: Cursor::Sparse { indices },
+        }
+    }
+
+    /// Create an iterator over the entities in a part of the packed entities of a query.
+    #[cfg(feature = "parallel")]
+    fn packed(query: &'query Query<'world, Q, F>, entities: &'query [Entity]) -> Self {
+        Self {
+            query,
+            cursor: Cursor::Packed {
+                entities: entities.iter(),
+            },
+        }
+    }
+
+    /// Create an iterator over the rows in the range of an archetype which matches a dense query.
+    #[cfg(feature = "parallel")]
+    fn rows(query: &'query Query<'world, Q, F>, archetype: usize, rows: Range<usize>) -> Self {
+        Self {
+            query,
+            cursor: Cursor::Dense {
+                archetypes: 0..0,
+                archetype,
+                entities: query.archetypes.get(archetype).entities(),
+                rows,
+            },
+        }
+    }
+}
+
+impl<'query, 'world, Q: QueryData, F: QueryFilter> Iterator for QueryIter<'query, 'world, Q, F> {
+    type Item = Q::Item<'query>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let archetypes = self.query.archetypes;
+        loop {
+            let location = match &mut self.cursor {
+                Cursor::Sparse { indices } => {
+                    // 生存していないエンティティには位置がない
+                    match archetypes.location(indices.next()?) {
+                        Some(location) => location,
+                        None => continue,
+                    }
+                }
+                Cursor::Packed { entities } => match archetypes.location(entities.next()?.index) {
+                    Some(location) => location,
+                    None => continue,
+                },
+                Cursor::Dense {
+                    archetypes: remaining,
+                    archetype,
+                    entities,
+                    rows,
+                } => match rows.next() {
+                    Some(row) => EntityLocation {
+                        index: entities[row].index,
+                        generation: entities[row].generation,
+                        archetype: *archetype,
+                        row,
+                    },
+                    None => {
+                        let next = remaining.next()?;
+                        let next_archetype = archetypes.get(next);
+                        if self.query.matches_archetype(next_archetype) {
+                            *archetype = next;
+                            *entities = next_archetype.entities();
+                            *rows = 0..entities.len();
+                        }
+                        continue;
+                    }
+                },
+            };
+            if !F::matches(&self.query.filter, location) {
+                continue;
+            }
+            // SAFETY:
+            // 各エンティティは一度しか取り出さない。
+            // 可変参照を含む場合、QueryIter は &mut Query からしか作られないので、同時に他のイテレータが存在することはない
+            if let Some(item) = unsafe { Q::fetch(&self.query.data, location) } {
+                return Some(item);
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.cursor {
+            Cursor::Sparse { indices } => (0, Some(indices.len())),
+            Cursor::Packed { entities } => (0, Some(entities.len())),
+            Cursor::Dense { .. } => (0, None),
+        }
+    }
+}
+
+/// Parallel iterator over the items of a [`Query`]
+///
+/// The entities are split into batches, which are processed on the thread pool of rayon.
+///
+/// ```
+/// use xanadu::ecs::{Query, World};
+///
+/// struct Particle(f32);
+///
+/// let mut world = World::builder().register_component::<Particle>().build();
+/// for i in 0..1000 {
+///     let entity = world.new_entity();
+///     world.attach_component(entity, Particle(i as f32));
+/// }
+///
+/// world.execute(|mut query: Query<'_, &mut Particle>| {
+///     query
+///         .par_iter_mut()
+///         .batch_size(64)
+///         .for_each(|mut particle| particle.0 *= 0.5);
+/// });
+/// ```
+#[cfg(feature = "parallel")]
+pub struct QueryParIter<'query, 'world, Q: QueryData, F: QueryFilter> {
+    query: &'query Query<'world, Q, F>,
+    batch_size: Option<usize>,
+}
+
+#[cfg(feature = "parallel")]
+impl<'query, 'world, Q: QueryData, F: QueryFilter> QueryParIter<'query, 'world, Q, F> {
+    /// Set the number of entities processed by a task.
+    ///
+    /// By default, the entities are split into about four batches per thread.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `batch_size` is 0.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be greater than 0");
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Call the closure with each item on the thread pool.
+    pub fn for_each(self, f: impl Fn(Q::Item<'query>) + Send + Sync)
+    where
+        Q::State<'world>: Sync,
+        F::State<'world>: Sync,
+    {
+        use rayon::prelude::*;
+
+        let query = self.query;
+        let default_batch_size = |len: usize| (len / (rayon::current_num_threads() * 4)).max(1);
+        // 各バッチは重ならない範囲のエンティティだけを取り出す
+        if query.is_dense() {
+            let archetypes: Vec<_> = query
+                .archetypes
+                .iter()
+                .filter(|(_, archetype)| query.matches_archetype(archetype))
+                .map(|(id, archetype)| (id, archetype.len()))
+                .collect();
+            let batch_size = self.batch_size.unwrap_or_else(|| {
+                default_batch_size(archetypes.iter().map(|&(_, len)| len).sum())
+            });
+            let batches: Vec<_> = archetypes
+                .into_iter()
+                .flat_map(|(id, len)| {
+                    (0..len)
+                        .step_by(batch_size)
+                        .map(move |start| (id, start..usize::min(len, start + batch_size)))
+                })
+                .collect();
+            batches
+                .into_par_iter()
+                .for_each(|(archetype, rows)| QueryIter::rows(query, archetype, rows).for_each(&f));
+        } else if let Some(entities) = query.packed_entities() {
+            let batch_size = self
+                .batch_size
+                .unwrap_or_else(|| default_batch_size(entities.len()));
+            entities
+                .par_chunks(batch_size)
+                .for_each(|entities| QueryIter::packed(query, entities).for_each(&f));
+        } else {
+            let len = query.sparse_len();
+            let batch_size = self.batch_size.unwrap_or_else(|| default_batch_size(len));
+            (0..len.div_ceil(batch_size))
+                .into_par_iter()
+                .for_each(|batch| {
+                    let start = batch * batch_size;
+                    let indices = start..usize::min(len, start + batch_size);
+                    QueryIter::indices(query, indices).for_each(&f);
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecs::{Or, StorageKind, With, Without};
+
+    fn world() -> World {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<u32>()
+            .register_component::<f32>()
+            .build();
+        for i in 0..6 {
+            let entity = world.new_entity();
+            world.attach_component(entity, i);
+            if i % 2 == 0 {
+                world.attach_component(entity, i as u32);
+            }
+            if i % 3 == 0 {
+                world.attach_component(entity, i as f32);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn single() {
+        let mut world = world();
+        world.execute(|query: Query<'_, &i32>| {
+            assert_eq!(
+                query.iter().copied().collect::<Vec<_>>(),
+                [0, 1, 2, 3, 4, 5]
+            );
+        });
+    }
+
+    #[test]
+    fn single_mut() {
+        let mut world = world();
+        world.execute(|mut query: Query<'_, &mut u32>| {
+            for mut value in &mut query {
+                *value += 10;
+            }
+        });
+        world.execute(|query: Query<'_, &u32>| {
+            assert_eq!(query.iter().copied().collect::<Vec<_>>(), [10, 12, 14]);
+        });
+    }
+
+    #[test]
+    fn tuple() {
+        let mut world = world();
+        world.execute(|mut query: Query<'_, (&i32, &mut u32, &f32)>| {
+            for (i, mut u, f) in query.iter_mut() {
+                *u += 100 + *i as u32 + *f as u32;
+            }
+        });
+        world.execute(|query: Query<'_, (&u32,)>| {
+            assert_eq!(query.iter().map(|(u,)| *u).collect::<Vec<_>>(), [100, 2, 4]);
+        });
+    }
+
+    #[test]
+    fn shared_borrows_of_same_component() {
+        let mut world = world();
+        world.execute(|query: Query<'_, (&i32, &i32)>| {
+            assert!(query.iter().all(|(a, b)| a == b));
+        });
+    }
+
+    #[test]
+    fn conflicting_borrows_of_same_component() {
+        let mut world = world();
+        assert_eq!(
+            world.try_execute(|_: Query<'_, (&i32, &mut i32)>| {}),
+            Err(Error::BorrowConflict("i32"))
+        );
+    }
+
+    type Twelve = (
+        &'static i32,
+        &'static i32,
+        &'static i32,
+        &'static i32,
+        &'static i32,
+        &'static i32,
+        &'static i32,
+        &'static i32,
+        &'static i32,
+        &'static i32,
+        &'static i32,
+        &'static f32,
+    );
+
+    #[test]
+    fn twelve_components() {
+        let mut world = world();
+        world.execute(|query: Query<'_, Twelve>| {
+            assert_eq!(query.iter().count(), 2);
+        });
+    }
+
+    #[test]
+    fn optional() {
+        let mut world = world();
+        world.execute(|query: Query<'_, (&i32, Option<&u32>)>| {
+            assert_eq!(
+                query
+                    .iter()
+                    .map(|(i, u)| (*i, u.copied()))
+                    .collect::<Vec<_>>(),
+                [
+                    (0, Some(0)),
+                    (1, None),
+                    (2, Some(2)),
+                    (3, None),
+                    (4, Some(4)),
+                    (5, None)
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn optional_mut() {
+        let mut world = world();
+        world.execute(|mut query: Query<'_, (&i32, Option<&mut f32>)>| {
+            for (i, f) in query.iter_mut() {
+                if let Some(mut f) = f {
+                    *f += *i as f32 + 0.5;
+                }
+            }
+        });
+        world.execute(|query: Query<'_, &f32>| {
+            assert_eq!(query.iter().copied().collect::<Vec<_>>(), [0.5, 6.5]);
+        });
+    }
+
+    #[test]
+    fn only_optional_skips_dead_entities() {
+        let mut world = world();
+        let entity = world.new_entity();
+        world.attach_component(entity, 100u32);
+        world.despawn(entity);
+        world.execute(|query: Query<'_, Option<&u32>>| {
+            assert_eq!(query.iter().count(), 6);
+            assert_eq!(query.iter().flatten().count(), 3);
+        });
+    }
+
+    #[test]
+    fn entity() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entities = (0..4)
+            .map(|i| {
+                let entity = world.new_entity();
+                world.attach_component(entity, i);
+                entity
+            })
+            .collect::<Vec<_>>();
+        world.despawn(entities[1]);
+        let reused = world.new_entity();
+        world.attach_component(reused, 4);
+
+        world.execute(|query: Query<'_, (Entity, &i32)>| {
+            assert_eq!(
+                query.iter().map(|(e, i)| (e, *i)).collect::<Vec<_>>(),
+                [
+                    (entities[0], 0),
+                    (reused, 4),
+                    (entities[2], 2),
+                    (entities[3], 3)
+                ]
+            );
+        });
+        world.execute(|query: Query<'_, Entity>| {
+            assert_eq!(query.iter().count(), 4);
+            assert!(query.iter().all(|e| e != entities[1]));
+        });
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn par_iter() {
+        use std::sync::atomic::{AtomicI32, Ordering};
+
+        let mut world = world();
+        world.execute(|query: Query<'_, (&i32, &u32)>| {
+            let sum = AtomicI32::new(0);
+            query.par_iter().batch_size(1).for_each(|(i, _)| {
+                sum.fetch_add(*i, Ordering::Relaxed);
+            });
+            assert_eq!(sum.into_inner(), 6);
+        });
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn par_iter_mut() {
+        let mut world = world();
+        for entity in (0..1000).map(|_| world.new_entity()).collect::<Vec<_>>() {
+            world.attach_component(entity, 1);
+        }
+        world.execute(|mut query: Query<'_, &mut i32>| {
+            query.par_iter_mut().for_each(|mut value| *value *= 2);
+        });
+        world.execute(|mut query: Query<'_, (&mut i32, Option<&f32>)>| {
+            query
+                .par_iter_mut()
+                .batch_size(7)
+                .for_each(|(mut value, f)| {
+                    if f.is_some() {
+                        *value = -1;
+                    }
+                });
+        });
+        world.execute(|query: Query<'_, &i32>| {
+            let values: Vec<_> = query.iter().copied().collect();
+            assert_eq!(&values[..6], [-1, 2, 4, -1, 8, 10]);
+            assert!(values[6..].iter().all(|&value| value == 2));
+        });
+    }
+
+    fn table_world() -> World {
+        let mut world = World::builder()
+            .register_component_with_storage::<i32>(StorageKind::Table)
+            .register_component_with_storage::<u32>(StorageKind::Table)
+            .register_component::<f32>()
+            .build();
+        for i in 0..6 {
+            let entity = world.new_entity();
+            world.attach_component(entity, i);
+            if i % 2 == 0 {
+                world.attach_component(entity, i as u32);
+            }
+            if i % 3 == 0 {
+                world.attach_component(entity, i as f32);
+            }
+        }
+        world
+    }
+
+    fn sorted<T: Ord>(mut vec: Vec<T>) -> Vec<T> {
+        vec.sort();
+        vec
+    }
+
+    #[test]
+    fn table() {
+        let mut world = table_world();
+        world.execute(|mut query: Query<'_, (&i32, &mut u32)>| {
+            for (i, mut u) in query.iter_mut() {
+                *u += 10 * *i as u32;
+            }
+        });
+        world.execute(|query: Query<'_, &u32>| {
+            assert_eq!(sorted(query.iter().copied().collect()), [0, 22, 44]);
+        });
+        world.execute(|query: Query<'_, &i32, Without<u32>>| {
+            assert_eq!(sorted(query.iter().copied().collect()), [1, 3, 5]);
+        });
+    }
+
+    type WithUOrF = Or<(With<u32>, With<f32>)>;
+
+    #[test]
+    fn table_mixed_with_sparse() {
+        let mut world = table_world();
+        world.execute(|query: Query<'_, (&i32, &f32)>| {
+            assert_eq!(sorted(query.iter().map(|(i, _)| *i).collect()), [0, 3]);
+        });
+        world.execute(|query: Query<'_, (&f32, Option<&u32>)>| {
+            let mut items: Vec<_> = query.iter().map(|(f, u)| (*f as i32, u.copied())).collect();
+            items.sort();
+            assert_eq!(items, [(0, Some(0)), (3, None)]);
+        });
+        world.execute(|query: Query<'_, &i32, WithUOrF>| {
+            assert_eq!(query.iter().count(), 4);
+        });
+    }
+
+    #[test]
+    fn table_after_structural_changes() {
+        let mut world = table_world();
+        let entities: Vec<_> = (0..6).filter_map(|i| world.entities.id_at(i)).collect();
+        world.detach_component::<u32>(entities[0]);
+        world.despawn(entities[2]);
+        world.attach_component(entities[1], 100u32);
+        world.execute(|query: Query<'_, (Entity, &i32, &u32)>| {
+            assert_eq!(
+                sorted(query.iter().map(|(_, i, u)| (*i, *u)).collect()),
+                [(1, 100), (4, 4)]
+            );
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn par_iter_table() {
+        let mut world = table_world();
+        for i in 0..1000 {
+            let entity = world.new_entity();
+            world.attach_component(entity, 1);
+            if i % 2 == 0 {
+                world.attach_component(entity, 1u32);
+            }
+        }
+        world.execute(|mut query: Query<'_, (&i32, &mut u32)>| {
+            query
+                .par_iter_mut()
+                .batch_size(16)
+                .for_each(|(i, mut u)| *u += *i as u32);
+        });
+        world.execute(|mut query: Query<'_, &mut u32>| {
+            query.par_iter_mut().for_each(|mut u| *u *= 2);
+        });
+        world.execute(|query: Query<'_, &u32>| {
+            let values = sorted(query.iter().copied().collect());
+            assert_eq!(values.len(), 503);
+            assert_eq!((values[0], values[501], values[502]), (0, 8, 16));
+            assert!(values[1..501].iter().all(|&value| value == 4));
+        });
+    }
+
+    #[test]
+    fn sparse_set() {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component_with_storage::<u32>(StorageKind::SparseSet)
+            .register_component_with_storage::<f32>(StorageKind::Table)
+            .build();
+        let entities: Vec<_> = (0..100).map(|_| world.new_entity()).collect();
+        for (i, &entity) in entities.iter().enumerate() {
+            world.attach_component(entity, i as i32);
+            if i % 15 == 0 {
+                world.attach_component(entity, i as u32);
+            }
+            if i % 2 == 0 {
+                world.attach_component(entity, i as f32);
+            }
+        }
+        world.detach_component::<u32>(entities[0]);
+        world.despawn(entities[60]);
+
+        world.execute(|mut query: Query<'_, (&i32, &mut u32)>| {
+            for (i, mut u) in query.iter_mut() {
+                *u += *i as u32;
+            }
+        });
+        world.execute(|query: Query<'_, (Entity, &u32)>| {
+            assert_eq!(
+                sorted(query.iter().map(|(_, u)| *u).collect()),
+                [30, 60, 90, 150, 180]
+            );
+        });
+        world.execute(|query: Query<'_, &i32, (With<u32>, With<f32>)>| {
+            assert_eq!(sorted(query.iter().copied().collect()), [30, 90]);
+        });
+        world.execute(|query: Query<'_, (&i32, Option<&u32>), Without<f32>>| {
+            assert_eq!(query.iter().filter(|(_, u)| u.is_some()).count(), 3);
+            assert_eq!(query.iter().count(), 50);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn par_iter_sparse_set() {
+        let mut world = World::builder()
+            .register_component_with_storage::<i32>(StorageKind::SparseSet)
+            .register_component::<u32>()
+            .build();
+        for i in 0..1000 {
+            let entity = world.new_entity();
+            world.attach_component(entity, 1u32);
+            if i % 10 == 0 {
+                world.attach_component(entity, i);
+            }
+        }
+        world.execute(|mut query: Query<'_, (&mut i32, &u32)>| {
+            query
+                .par_iter_mut()
+                .batch_size(7)
+                .for_each(|(mut i, u)| *i += *u as i32);
+        });
+        world.execute(|query: Query<'_, &i32>| {
+            let values = sorted(query.iter().copied().collect());
+            assert_eq!(values, (0..100).map(|i| i * 10 + 1).collect::<Vec<_>>());
+        });
+    }
+}
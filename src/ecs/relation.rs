@@ -0,0 +1,321 @@
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+};
+
+use super::{Access, Entity, FromWorld, SystemTicks, World};
+use crate::Error;
+
+/// Represents a kind of directed link from a source entity to a target entity, such as "targets",
+/// "owned by" or "docked at".
+///
+/// Relation types are usually empty marker types, and must be registered with
+/// [`WorldBuilder::register_relation`](crate::ecs::WorldBuilder::register_relation).
+pub trait Relation: Send + Sync + 'static {}
+
+impl<T> Relation for T where T: Send + Sync + 'static {}
+
+/// What happens to the entities on the other side of a relation when an entity is despawned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cleanup {
+    /// Only the pairs with the despawned entity are removed.
+    #[default]
+    Unrelate,
+    /// The entities related with the despawned entity are despawned as well.
+    Despawn,
+}
+
+/// Cleanup policies of a relation type
+///
+/// ```
+/// use xanadu::ecs::{Cleanup, RelationPolicy, World};
+///
+/// struct OwnedBy;
+///
+/// // Items are despawned with their owner.
+/// let mut world = World::builder()
+///     .register_relation::<OwnedBy>(RelationPolicy {
+///         on_despawn_target: Cleanup::Despawn,
+///         ..RelationPolicy::default()
+///     })
+///     .build();
+/// let owner = world.new_entity();
+/// let item = world.new_entity();
+/// world.relate::<OwnedBy>(item, owner);
+///
+/// world.despawn(owner);
+/// assert!(!world.is_alive(item));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RelationPolicy {
+    /// Applied to the targets of an entity when it is despawned
+    pub on_despawn_source: Cleanup,
+    /// Applied to the sources of an entity when it is despawned
+    pub on_despawn_target: Cleanup,
+}
+
+/// Pairs of a relation type, indexed in both directions
+pub struct RelationStorage {
+    targets: HashMap<Entity, Vec<Entity>>,
+    sources: HashMap<Entity, Vec<Entity>>,
+    policy: RelationPolicy,
+}
+
+impl RelationStorage {
+    fn new(policy: RelationPolicy) -> Self {
+        Self {
+            targets: HashMap::new(),
+            sources: HashMap::new(),
+            policy,
+        }
+    }
+
+    /// Add the pair, and return false if it already exists.
+    pub(crate) fn insert(&mut self, source: Entity, target: Entity) -> bool {
+        let targets = self.targets.entry(source).or_default();
+        if targets.contains(&target) {
+            return false;
+        }
+        targets.push(target);
+        self.sources.entry(target).or_default().push(source);
+        true
+    }
+
+    /// Remove the pair, and return false if it does not exist.
+    pub(crate) fn remove(&mut self, source: Entity, target: Entity) -> bool {
+        if !remove_from(&mut self.targets, source, target) {
+            return false;
+        }
+        remove_from(&mut self.sources, target, source);
+        true
+    }
+
+    pub(crate) fn targets(&self, source: Entity) -> &[Entity] {
+        self.targets.get(&source).map_or(&[], Vec::as_slice)
+    }
+
+    pub(crate) fn sources(&self, target: Entity) -> &[Entity] {
+        self.sources.get(&target).map_or(&[], Vec::as_slice)
+    }
+
+    /// Remove every pair with the entity, and push the entities to despawn by the policy to
+    /// `despawn`.
+    fn remove_entity(&mut self, entity: Entity, despawn: &mut Vec<Entity>) {
+        let targets = self.targets.remove(&entity).unwrap_or_default();
+        for &target in &targets {
+            remove_from(&mut self.sources, target, entity);
+        }
+        let sources = self.sources.remove(&entity).unwrap_or_default();
+        for &source in &sources {
+            remove_from(&mut self.targets, source, entity);
+        }
+        if self.policy.on_despawn_source == Cleanup::Despawn {
+            despawn.extend(targets);
+        }
+        if self.policy.on_despawn_target == Cleanup::Despawn {
+            despawn.extend(sources);
+        }
+    }
+}
+
+fn remove_from(map: &mut HashMap<Entity, Vec<Entity>>, key: Entity, value: Entity) -> bool {
+    let Some(values) = map.get_mut(&key) else {
+        return false;
+    };
+    let Some(position) = values.iter().position(|&entity| entity == value) else {
+        return false;
+    };
+    // 関係を結んだ順番を保つ
+    values.remove(position);
+    if values.is_empty() {
+        map.remove(&key);
+    }
+    true
+}
+
+/// Pairs of every relation type in a world
+#[derive(Default)]
+pub struct Relations {
+    map: HashMap<TypeId, RelationStorage>,
+}
+
+impl Relations {
+    pub(crate) fn register<R: Relation>(&mut self, policy: RelationPolicy) {
+        self.map
+            .insert(TypeId::of::<R>(), RelationStorage::new(policy));
+    }
+
+    pub(crate) fn get<R: Relation>(&self) -> Result<&RelationStorage, Error> {
+        self.map
+            .get(&TypeId::of::<R>())
+            .ok_or_else(|| Error::UnregisteredRelation(type_name::<R>()))
+    }
+
+    pub(crate) fn get_mut<R: Relation>(&mut self) -> Result<&mut RelationStorage, Error> {
+        self.map
+            .get_mut(&TypeId::of::<R>())
+            .ok_or_else(|| Error::UnregisteredRelation(type_name::<R>()))
+    }
+
+    /// Remove every pair with the entity.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the entities to despawn by the policies of the relation types.
+    pub(crate) fn remove_entity(&mut self, entity: Entity) -> Vec<Entity> {
+        let mut despawn = Vec::new();
+        for storage in self.map.values_mut() {
+            storage.remove_entity(entity, &mut despawn);
+        }
+        despawn
+    }
+}
+
+/// System parameter which looks up the pairs of the relation type `R`
+///
+/// Pairs are only changed by the methods of [`World`] such as [`World::relate`], so this doesn't
+/// conflict with other parameters. Use [`Commands`](crate::ecs::Commands) to change them from
+/// systems.
+pub struct Related<'world, R: Relation> {
+    storage: &'world RelationStorage,
+    _marker: PhantomData<fn() -> R>,
+}
+
+impl<'world, R: Relation> Related<'world, R> {
+    /// Returns the targets of the source entity in the order they were related.
+    pub fn targets(&self, source: Entity) -> &'world [Entity] {
+        self.storage.targets(source)
+    }
+
+    /// Returns the sources of the target entity in the order they were related.
+    pub fn sources(&self, target: Entity) -> &'world [Entity] {
+        self.storage.sources(target)
+    }
+
+    /// Check if the source entity is related to the target entity.
+    pub fn contains(&self, source: Entity, target: Entity) -> bool {
+        self.targets(source).contains(&target)
+    }
+}
+
+impl<R: Relation> FromWorld for Related<'_, R> {
+    type Item<'world> = Related<'world, R>;
+
+    fn access(_access: &mut Access) {}
+
+    fn try_from_world(world: &World, _ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        Ok(Related {
+            storage: world.relations.get::<R>()?,
+            _marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecs::Commands;
+
+    struct Targets;
+    struct DockedAt;
+
+    fn world(policy: RelationPolicy) -> World {
+        World::builder()
+            .register_relation::<Targets>(RelationPolicy::default())
+            .register_relation::<DockedAt>(policy)
+            .build()
+    }
+
+    #[test]
+    fn relate_and_unrelate() {
+        let mut world = world(RelationPolicy::default());
+        let [a, b, c] = [(); 3].map(|()| world.new_entity());
+        assert!(world.relate::<Targets>(a, b));
+        assert!(world.relate::<Targets>(a, c));
+        assert!(!world.relate::<Targets>(a, b));
+        world.relate::<Targets>(c, b);
+        world.relate::<DockedAt>(b, a);
+        assert_eq!(world.targets::<Targets>(a), [b, c]);
+        assert_eq!(world.sources::<Targets>(b), [a, c]);
+        assert_eq!(world.targets::<DockedAt>(a), []);
+
+        assert!(world.unrelate::<Targets>(a, b));
+        assert!(!world.unrelate::<Targets>(a, b));
+        assert_eq!(world.targets::<Targets>(a), [c]);
+        assert_eq!(world.sources::<Targets>(b), [c]);
+        world.execute(
+            |targets: Related<'_, Targets>, docked: Related<'_, DockedAt>| {
+                assert!(targets.contains(a, c));
+                assert!(!targets.contains(c, a));
+                assert_eq!(docked.sources(a), [b]);
+            },
+        );
+    }
+
+    #[test]
+    fn unrelate_on_despawn() {
+        let mut world = world(RelationPolicy::default());
+        let [a, b, c] = [(); 3].map(|()| world.new_entity());
+        world.relate::<Targets>(a, b);
+        world.relate::<Targets>(b, c);
+        world.despawn(b);
+        assert!(world.is_alive(a));
+        assert!(world.is_alive(c));
+        assert_eq!(world.targets::<Targets>(a), []);
+        assert_eq!(world.sources::<Targets>(c), []);
+    }
+
+    #[test]
+    fn despawn_sources() {
+        let mut world = world(RelationPolicy {
+            on_despawn_target: Cleanup::Despawn,
+            ..RelationPolicy::default()
+        });
+        let [station, ship, shuttle, other] = [(); 4].map(|()| world.new_entity());
+        world.relate::<DockedAt>(ship, station);
+        world.relate::<DockedAt>(shuttle, ship);
+        world.relate::<Targets>(other, ship);
+        world.despawn(station);
+        assert!(!world.is_alive(ship));
+        assert!(!world.is_alive(shuttle));
+        assert!(world.is_alive(other));
+        assert_eq!(world.targets::<Targets>(other), []);
+    }
+
+    #[test]
+    fn despawn_targets() {
+        let mut world = world(RelationPolicy {
+            on_despawn_source: Cleanup::Despawn,
+            ..RelationPolicy::default()
+        });
+        let [a, b] = [(); 2].map(|()| world.new_entity());
+        world.relate::<DockedAt>(a, b);
+        world.relate::<DockedAt>(b, a);
+        world.despawn(b);
+        assert!(!world.is_alive(a));
+    }
+
+    #[test]
+    fn commands_and_errors() {
+        let mut world = world(RelationPolicy::default());
+        let [a, b] = [(); 2].map(|()| world.new_entity());
+        world.execute(|mut commands: Commands<'_>| {
+            commands
+                .entity(a)
+                .relate::<Targets>(b)
+                .relate::<DockedAt>(b);
+            commands.entity(a).unrelate::<DockedAt>(b);
+        });
+        assert_eq!(world.targets::<Targets>(a), [b]);
+        assert_eq!(world.targets::<DockedAt>(a), []);
+
+        world.despawn(b);
+        assert_eq!(world.try_relate::<Targets>(a, b), Err(Error::DeadEntity(b)));
+        assert_eq!(
+            world.try_relate::<u32>(a, a),
+            Err(Error::UnregisteredRelation("u32"))
+        );
+        assert!(world.try_execute(|_: Related<'_, u32>| {}).is_err());
+    }
+}
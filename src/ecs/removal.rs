@@ -0,0 +1,215 @@
+use std::{any::TypeId, collections::HashMap, marker::PhantomData, slice};
+
+use super::{Access, Component, Entity, FromWorld, SystemTicks, Tick, World};
+use crate::Error;
+
+/// Log of the components which were detached or whose entities were despawned
+pub struct Removals {
+    log: HashMap<TypeId, Vec<(Entity, Tick)>>,
+    // 前回 clear したときのティック。これより前の記録は次の clear で捨てる
+    cleared: Tick,
+}
+
+impl Removals {
+    pub(crate) fn new() -> Self {
+        Self {
+            log: HashMap::new(),
+            cleared: Tick::default(),
+        }
+    }
+
+    pub(crate) fn record(&mut self, type_id: TypeId, entity: Entity, tick: Tick) {
+        self.log.entry(type_id).or_default().push((entity, tick));
+    }
+
+    fn get(&self, type_id: TypeId) -> &[(Entity, Tick)] {
+        self.log.get(&type_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Drop the removals recorded before the previous call.
+    ///
+    /// Each removal is kept until the second call after it, so that every system in a schedule can
+    /// see it once even if it was recorded in the middle of a run.
+    pub(crate) fn clear(&mut self, tick: Tick) {
+        let cleared = self.cleared;
+        for removals in self.log.values_mut() {
+            removals.retain(|&(_, removed)| !cleared.is_newer_than(removed));
+        }
+        self.cleared = tick;
+    }
+}
+
+/// System parameter which yields the entities whose component `T` was detached, or which were
+/// despawned with `T`, since the last run of the system
+///
+/// Removals are kept until [`World::clear_trackers`] is called twice after them, which
+/// [`Schedule`](crate::ecs::Schedule) does at the end of each run. A system executed by
+/// [`World::execute`] sees every removal which is still kept.
+///
+/// ```
+/// use xanadu::ecs::{Entity, RemovedComponents, World};
+///
+/// struct RigidBody;
+///
+/// let mut world = World::builder().register_component::<RigidBody>().build();
+/// let entity = world.new_entity();
+/// world.attach_component(entity, RigidBody);
+/// world.despawn(entity);
+///
+/// world.execute(|removed: RemovedComponents<'_, RigidBody>| {
+///     assert_eq!(removed.iter().collect::<Vec<Entity>>(), [entity]);
+/// });
+/// ```
+pub struct RemovedComponents<'world, T: Component> {
+    removals: &'world [(Entity, Tick)],
+    last_run: Tick,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'world, T: Component> RemovedComponents<'world, T> {
+    /// Returns an iterator over the entities whose component was removed since the last run.
+    ///
+    /// The same entity is yielded more than once if the component was removed more than once.
+    pub fn iter(&self) -> RemovedIter<'world> {
+        RemovedIter {
+            removals: self.removals.iter(),
+            last_run: self.last_run,
+        }
+    }
+
+    /// Returns the number of the removals since the last run.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Check if there are no removals since the last run.
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+/// Iterator over the entities yielded by [`RemovedComponents`]
+pub struct RemovedIter<'world> {
+    removals: slice::Iter<'world, (Entity, Tick)>,
+    last_run: Tick,
+}
+
+impl Iterator for RemovedIter<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let last_run = self.last_run;
+        self.removals
+            .find(|(_, tick)| tick.is_newer_than(last_run))
+            .map(|&(entity, _)| entity)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.removals.size_hint().1)
+    }
+}
+
+impl<'world, T: Component> IntoIterator for &RemovedComponents<'world, T> {
+    type Item = Entity;
+    type IntoIter = RemovedIter<'world>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Component> FromWorld for RemovedComponents<'_, T> {
+    type Item<'world> = RemovedComponents<'world, T>;
+
+    // 記録は &mut World からしか変更されないので、他のパラメータと競合しない
+    fn access(_access: &mut Access) {}
+
+    fn try_from_world(world: &World, ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        Ok(RemovedComponents {
+            removals: world.removals.get(TypeId::of::<T>()),
+            last_run: ticks.last_run,
+            _marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecs::{Commands, ResMut, Schedule, StorageKind};
+
+    #[derive(Default)]
+    struct Seen(Vec<Entity>);
+
+    fn record(removed: RemovedComponents<'_, i32>, mut seen: ResMut<'_, Seen>) {
+        seen.0 = removed.iter().collect();
+    }
+
+    #[test]
+    fn detach_and_despawn() {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component_with_storage::<f32>(StorageKind::Table)
+            .build();
+        world.insert_resource(Seen::default());
+        let mut schedule = Schedule::new();
+        schedule.add_system(record);
+        let mut run = |world: &mut World| {
+            schedule.run(world);
+            world.get_resource::<Seen>().unwrap().0.clone()
+        };
+
+        let a = world.new_entity();
+        let b = world.new_entity();
+        let c = world.new_entity();
+        for entity in [a, b, c] {
+            world.attach_component(entity, 1);
+            world.attach_component(entity, 1.0f32);
+        }
+        assert_eq!(run(&mut world), []);
+
+        world.detach_component::<i32>(a);
+        world.detach_component::<i32>(a);
+        world.despawn(b);
+        assert_eq!(run(&mut world), [a, b]);
+        assert_eq!(run(&mut world), []);
+
+        world.execute(|mut commands: Commands<'_>| commands.despawn(c));
+        assert_eq!(run(&mut world), [c]);
+    }
+
+    #[test]
+    fn table_components() {
+        let mut world = World::builder()
+            .register_component_with_storage::<i32>(StorageKind::Table)
+            .build();
+        let a = world.new_entity();
+        let b = world.new_entity();
+        world.attach_component(a, 1);
+        world.attach_component(b, 2);
+        world.detach_component::<i32>(a);
+        world.despawn(b);
+        world.execute(|removed: RemovedComponents<'_, i32>| {
+            assert_eq!(removed.iter().collect::<Vec<_>>(), [a, b]);
+            assert_eq!(removed.len(), 2);
+        });
+    }
+
+    #[test]
+    fn clear() {
+        let entity = |index| Entity {
+            index,
+            generation: 0,
+        };
+        let mut removals = Removals::new();
+        let type_id = TypeId::of::<i32>();
+        removals.record(type_id, entity(0), Tick(1));
+        removals.clear(Tick(2));
+        removals.record(type_id, entity(1), Tick(2));
+        assert_eq!(removals.get(type_id).len(), 2);
+        removals.clear(Tick(3));
+        assert_eq!(removals.get(type_id), [(entity(1), Tick(2))]);
+        removals.clear(Tick(4));
+        assert!(removals.get(type_id).is_empty());
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use super::{Access, FromWorld, MaybeSendSync, SystemTicks, World};
+use crate::{
+    collections::{AtomicRef, AtomicRefCell, AtomicRefMut},
+    Error,
+};
+
+/// Represents a global value stored in a world, such as frame time or input state.
+///
+/// Unlike components, a world holds at most one value for each resource type, and resources don't
+/// have to be registered.
+///
+/// With the `parallel` feature, resources must be `Send` and `Sync`, because systems which access
+/// them may be executed on other threads. See [`MaybeSendSync`].
+pub trait Resource: MaybeSendSync + 'static {}
+
+impl<T> Resource for T where T: MaybeSendSync + 'static {}
+
+#[cfg(feature = "parallel")]
+type BoxedResource = Box<dyn Any + Send + Sync>;
+#[cfg(not(feature = "parallel"))]
+type BoxedResource = Box<dyn Any>;
+
+/// System parameter which borrows the resource `T` immutably
+///
+/// The system fails to execute if the resource does not exist. Use `Option<Res<'_, T>>` if the
+/// resource may be missing.
+pub struct Res<'world, T: Resource> {
+    value: AtomicRef<'world, T>,
+}
+
+impl<T: Resource> Deref for Res<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T: Resource + fmt::Debug> fmt::Debug for Res<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Res").field(&*self.value).finish()
+    }
+}
+
+impl<T: Resource> FromWorld for Res<'_, T> {
+    type Item<'world> = Res<'world, T>;
+
+    fn access(access: &mut Access) {
+        access.add_resource_read::<T>();
+    }
+
+    fn try_from_world(world: &World, _ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        Ok(Res {
+            value: world.resources.borrow::<T>()?,
+        })
+    }
+}
+
+/// System parameter which borrows the resource `T` mutably
+///
+/// The system fails to execute if the resource does not exist. Use `Option<ResMut<'_, T>>` if the
+/// resource may be missing.
+pub struct ResMut<'world, T: Resource> {
+    value: AtomicRefMut<'world, T>,
+}
+
+impl<T: Resource> Deref for ResMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T: Resource> DerefMut for ResMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<T: Resource + fmt::Debug> fmt::Debug for ResMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ResMut").field(&*self.value).finish()
+    }
+}
+
+impl<T: Resource> FromWorld for ResMut<'_, T> {
+    type Item<'world> = ResMut<'world, T>;
+
+    fn access(access: &mut Access) {
+        access.add_resource_write::<T>();
+    }
+
+    fn try_from_world(world: &World, _ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        Ok(ResMut {
+            value: world.resources.borrow_mut::<T>()?,
+        })
+    }
+}
+
+/// Takes the resource if it exists, or passes None to the system instead of failing.
+impl<'w, T: Resource> FromWorld for Option<Res<'w, T>> {
+    type Item<'world> = Option<Res<'world, T>>;
+
+    fn access(access: &mut Access) {
+        Res::<'w, T>::access(access);
+    }
+
+    fn try_from_world(world: &World, ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        if !world.resources.contains::<T>() {
+            return Ok(None);
+        }
+        Res::<'w, T>::try_from_world(world, ticks).map(Some)
+    }
+}
+
+/// Takes the resource if it exists, or passes None to the system instead of failing.
+impl<'w, T: Resource> FromWorld for Option<ResMut<'w, T>> {
+    type Item<'world> = Option<ResMut<'world, T>>;
+
+    fn access(access: &mut Access) {
+        ResMut::<'w, T>::access(access);
+    }
+
+    fn try_from_world(world: &World, ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        if !world.resources.contains::<T>() {
+            return Ok(None);
+        }
+        ResMut::<'w, T>::try_from_world(world, ticks).map(Some)
+    }
+}
+
+pub struct Resources {
+    map: HashMap<TypeId, AtomicRefCell<BoxedResource>>,
+}
+
+impl Resources {
+    pub(crate) fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn insert<T: Resource>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), AtomicRefCell::new(Box::new(value)))
+            .map(|old| {
+                // SAFETY:
+                // self.map[TypeId::of<T>] には T が入っているので、ダウンキャストは必ず成功する
+                *unsafe { old.into_inner().downcast::<T>().unwrap_unchecked() }
+            })
+    }
+
+    pub(crate) fn remove<T: Resource>(&mut self) -> Option<T> {
+        self.map.remove(&TypeId::of::<T>()).map(|old| {
+            // SAFETY:
+            // self.map[TypeId::of<T>] には T が入っているので、ダウンキャストは必ず成功する
+            *unsafe { old.into_inner().downcast::<T>().unwrap_unchecked() }
+        })
+    }
+
+    pub(crate) fn contains<T: Resource>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    pub(crate) fn get_mut<T: Resource>(&mut self) -> Option<&mut T> {
+        self.map.get_mut(&TypeId::of::<T>()).map(|refcell| {
+            // SAFETY:
+            // self.map[TypeId::of<T>] には T が入っているので、ダウンキャストは必ず成功する
+            unsafe { refcell.get_mut().downcast_mut::<T>().unwrap_unchecked() }
+        })
+    }
+
+    pub(crate) fn borrow<T: Resource>(&self) -> Result<AtomicRef<'_, T>, Error> {
+        let refcell = self.map.get(&TypeId::of::<T>()).ok_or_else(missing::<T>)?;
+        let borrow = refcell.try_borrow().map_err(|_| borrow_conflict::<T>())?;
+        Ok(AtomicRef::map(borrow, |value| {
+            // SAFETY:
+            // self.map[TypeId::of<T>] には T が入っているので、ダウンキャストは必ず成功する
+            unsafe { value.downcast_ref::<T>().unwrap_unchecked() }
+        }))
+    }
+
+    pub(crate) fn borrow_mut<T: Resource>(&self) -> Result<AtomicRefMut<'_, T>, Error> {
+        let refcell = self.map.get(&TypeId::of::<T>()).ok_or_else(missing::<T>)?;
+        let borrow = refcell
+            .try_borrow_mut()
+            .map_err(|_| borrow_conflict::<T>())?;
+        Ok(AtomicRefMut::map(borrow, |value| {
+            // SAFETY:
+            // self.map[TypeId::of<T>] には T が入っているので、ダウンキャストは必ず成功する
+            unsafe { value.downcast_mut::<T>().unwrap_unchecked() }
+        }))
+    }
+}
+
+fn missing<T: Resource>() -> Error {
+    Error::MissingResource(type_name::<T>())
+}
+
+fn borrow_conflict<T: Resource>() -> Error {
+    Error::ResourceBorrowConflict(type_name::<T>())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut resources = Resources::new();
+        assert_eq!(resources.insert(1i32), None);
+        assert_eq!(resources.insert(2i32), Some(1));
+        assert!(resources.contains::<i32>());
+        assert!(!resources.contains::<f32>());
+        *resources.get_mut::<i32>().unwrap() += 1;
+        assert_eq!(resources.remove::<i32>(), Some(3));
+        assert_eq!(resources.remove::<i32>(), None);
+    }
+
+    #[test]
+    fn borrow() {
+        let mut resources = Resources::new();
+        resources.insert(1i32);
+        assert_eq!(
+            resources.borrow::<f32>().err(),
+            Some(Error::MissingResource("f32"))
+        );
+        let shared = resources.borrow::<i32>().unwrap();
+        assert!(resources.borrow::<i32>().is_ok());
+        assert_eq!(
+            resources.borrow_mut::<i32>().err(),
+            Some(Error::ResourceBorrowConflict("i32"))
+        );
+        drop(shared);
+        assert!(resources.borrow_mut::<i32>().is_ok());
+    }
+}
@@ -0,0 +1,513 @@
+use std::{any::type_name, fmt};
+
+#[cfg(feature = "parallel")]
+use super::Access;
+use super::{MaybeSend, System, Tick, World};
+use crate::Error;
+
+#[cfg(feature = "parallel")]
+type BoxedSystem = Box<dyn FnMut(&World, Tick) -> Result<Tick, Error> + Send>;
+#[cfg(not(feature = "parallel"))]
+type BoxedSystem = Box<dyn FnMut(&World, Tick) -> Result<Tick, Error>>;
+
+struct SystemEntry {
+    name: &'static str,
+    system: BoxedSystem,
+    // 前回実行したときのティック。Added や Changed の判定に使う
+    last_run: Tick,
+    #[cfg(feature = "parallel")]
+    access: Access,
+    labels: Vec<&'static str>,
+    before: Vec<&'static str>,
+    after: Vec<&'static str>,
+}
+
+/// Ordered set of systems which are executed on a world each tick
+///
+/// Systems are executed in the order they were added, unless the order is constrained by
+/// [`SystemConfig::before`] and [`SystemConfig::after`]. The commands recorded by each system are
+/// applied before the next system is executed.
+///
+/// Each system remembers the tick of its last run, so [`Added`](crate::ecs::Added) and
+/// [`Changed`](crate::ecs::Changed) filters match the components attached or mutated since then,
+/// and [`EventReader`](crate::ecs::EventReader) reads the events sent since then.
+///
+/// With the `parallel` feature, [`Schedule::run_parallel`] executes systems which don't conflict
+/// with each other on a thread pool.
+///
+/// ```
+/// use xanadu::ecs::{Query, Schedule, World};
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// fn movement(mut query: Query<'_, (&mut Position, &Velocity)>) {
+///     for (mut pos, vel) in query.iter_mut() {
+///         pos.0 += vel.0;
+///     }
+/// }
+///
+/// fn print(query: Query<'_, &Position>) {
+///     for pos in query.iter() {
+///         println!("{}", pos.0);
+///     }
+/// }
+///
+/// let mut world = World::builder()
+///     .register_component::<Position>()
+///     .register_component::<Velocity>()
+///     .build();
+/// let mut schedule = Schedule::new();
+/// schedule.add_system(print).after("movement");
+/// schedule.add_system(movement).label("movement");
+/// for _ in 0..3 {
+///     schedule.run(&mut world);
+/// }
+/// ```
+#[derive(Default)]
+pub struct Schedule {
+    systems: Vec<SystemEntry>,
+    // 実行計画。システムが追加されると None に戻る
+    plan: Option<Plan>,
+}
+
+struct Plan {
+    // 実行順に並べたシステムのインデックス
+    order: Vec<usize>,
+    // 同時に実行できるシステムの組。前の組が全て終わってから次の組を実行する
+    #[cfg(feature = "parallel")]
+    batches: Vec<Vec<usize>>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a system to the schedule, and return [`SystemConfig`] to set its label and ordering
+    /// constraints.
+    ///
+    /// With the `parallel` feature, the system must be `Send`, because it may be executed on
+    /// another thread.
+    pub fn add_system<T>(
+        &mut self,
+        mut system: impl System<T> + MaybeSend + 'static,
+    ) -> SystemConfig<'_> {
+        self.plan = None;
+        self.systems.push(SystemEntry {
+            name: system_name(&system),
+            #[cfg(feature = "parallel")]
+            access: system.access(),
+            system: Box::new(move |world, last_run| system.try_run_since(world, last_run)),
+            last_run: Tick::default(),
+            labels: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+        });
+        SystemConfig {
+            // 直前に push したので必ず存在する
+            entry: self.systems.last_mut().unwrap(),
+        }
+    }
+
+    /// Execute every system once.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the systems cannot be ordered, or if the parameters of a system cannot be taken
+    /// from the world. Use [`Schedule::try_run`] to handle the error instead.
+    pub fn run(&mut self, world: &mut World) {
+        self.try_run(world).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Execute every system once, or return an error if the systems cannot be ordered or the
+    /// parameters of a system cannot be taken from the world.
+    ///
+    /// ## Returns
+    ///
+    /// Returns [`Error::UnknownLabel`] if an ordering constraint refers to a label which no system
+    /// has, and [`Error::CyclicDependency`] if the ordering constraints form a cycle. If a system
+    /// fails, the remaining systems are not executed.
+    pub fn try_run(&mut self, world: &mut World) -> Result<(), Error> {
+        self.prepare()?;
+        let Self { systems, plan } = self;
+        for &index in plan.iter().flat_map(|plan| &plan.order) {
+            let entry = &mut systems[index];
+            let result = (entry.system)(world, entry.last_run);
+            world.flush();
+            entry.last_run = result?;
+        }
+        world.update_events();
+        world.clear_trackers();
+        Ok(())
+    }
+
+    /// Execute every system once, running systems which don't conflict with each other at the same
+    /// time on the thread pool of rayon.
+    ///
+    /// Two systems conflict if one of them writes a component or resource which the other accesses.
+    /// Conflicting systems are executed in the order [`Schedule::run`] would execute them. The
+    /// commands are applied after each group of systems executed at the same time, instead of after
+    /// each system.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the systems cannot be ordered, or if the parameters of a system cannot be taken
+    /// from the world. Use [`Schedule::try_run_parallel`] to handle the error instead.
+    #[cfg(feature = "parallel")]
+    pub fn run_parallel(&mut self, world: &mut World) {
+        self.try_run_parallel(world)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Execute every system once in parallel like [`Schedule::run_parallel`], or return an error if
+    /// the systems cannot be ordered or the parameters of a system cannot be taken from the world.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the same errors as [`Schedule::try_run`]. If a system fails, the systems executed at
+    /// the same time still finish, but the later ones are not executed.
+    #[cfg(feature = "parallel")]
+    pub fn try_run_parallel(&mut self, world: &mut World) -> Result<(), Error> {
+        use rayon::prelude::*;
+
+        self.prepare()?;
+        let Self { systems, plan } = self;
+        for batch in plan.iter().flat_map(|plan| &plan.batches) {
+            let shared: &World = world;
+            let results: Vec<_> = systems
+                .iter_mut()
+                .enumerate()
+                .filter(|(index, _)| batch.contains(index))
+                .map(|(_, entry)| entry)
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .map(|entry| {
+                    let tick = (entry.system)(shared, entry.last_run)?;
+                    entry.last_run = tick;
+                    Ok(())
+                })
+                .collect();
+            world.flush();
+            results.into_iter().collect::<Result<(), Error>>()?;
+        }
+        world.update_events();
+        world.clear_trackers();
+        Ok(())
+    }
+
+    fn prepare(&mut self) -> Result<(), Error> {
+        if self.plan.is_none() {
+            self.plan = Some(self.sort()?);
+        }
+        Ok(())
+    }
+
+    /// Return the indices of the systems in the order they should be executed.
+    fn sort(&self) -> Result<Plan, Error> {
+        let len = self.systems.len();
+        // dependents[i]: i の後に実行されるシステム
+        let mut dependents = vec![Vec::new(); len];
+        let mut dependencies = vec![0usize; len];
+        for (index, entry) in self.systems.iter().enumerate() {
+            for &label in &entry.before {
+                for other in self.labeled(label)? {
+                    dependents[index].push(other);
+                    dependencies[other] += 1;
+                }
+            }
+            for &label in &entry.after {
+                for other in self.labeled(label)? {
+                    dependents[other].push(index);
+                    dependencies[index] += 1;
+                }
+            }
+        }
+        #[cfg(feature = "parallel")]
+        let predecessors = {
+            let mut predecessors = vec![Vec::new(); len];
+            for (index, dependents) in dependents.iter().enumerate() {
+                for &other in dependents {
+                    predecessors[other].push(index);
+                }
+            }
+            predecessors
+        };
+
+        // 制約のない範囲では追加された順を保つため、実行可能なもののうち最も先に追加されたものを選ぶ
+        let mut order = Vec::with_capacity(len);
+        let mut done = vec![false; len];
+        while let Some(index) = (0..len).find(|&i| !done[i] && dependencies[i] == 0) {
+            done[index] = true;
+            order.push(index);
+            for &other in &dependents[index] {
+                dependencies[other] -= 1;
+            }
+        }
+        if order.len() < len {
+            let names = (0..len)
+                .filter(|&i| !done[i])
+                .map(|i| self.systems[i].name)
+                .collect();
+            return Err(Error::CyclicDependency(names));
+        }
+
+        #[cfg(feature = "parallel")]
+        let batches = {
+            // 明示的な依存先と、先に実行されるはずの競合するシステムより後の組に入れる
+            let mut batch_of = vec![0; len];
+            let mut batches: Vec<Vec<usize>> = Vec::new();
+            for (position, &index) in order.iter().enumerate() {
+                let access = &self.systems[index].access;
+                let batch = order[..position]
+                    .iter()
+                    .filter(|&&other| {
+                        predecessors[index].contains(&other)
+                            || access.conflict_with(&self.systems[other].access).is_some()
+                    })
+                    .map(|&other| batch_of[other] + 1)
+                    .max()
+                    .unwrap_or(0);
+                batch_of[index] = batch;
+                if batches.len() <= batch {
+                    batches.resize_with(batch + 1, Vec::new);
+                }
+                batches[batch].push(index);
+            }
+            batches
+        };
+
+        Ok(Plan {
+            order,
+            #[cfg(feature = "parallel")]
+            batches,
+        })
+    }
+
+    fn labeled(&self, label: &'static str) -> Result<Vec<usize>, Error> {
+        let indices: Vec<_> = self
+            .systems
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.labels.contains(&label))
+            .map(|(index, _)| index)
+            .collect();
+        if indices.is_empty() {
+            return Err(Error::UnknownLabel(label));
+        }
+        Ok(indices)
+    }
+}
+
+impl fmt::Debug for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.systems.iter().map(|entry| entry.name))
+            .finish()
+    }
+}
+
+/// Label and ordering constraints of a system in a [`Schedule`]
+///
+/// Returned by [`Schedule::add_system`].
+pub struct SystemConfig<'a> {
+    entry: &'a mut SystemEntry,
+}
+
+impl SystemConfig<'_> {
+    /// Give the system a label, which other systems can refer to in their ordering constraints.
+    ///
+    /// Several systems can share the same label, and a system can have several labels.
+    pub fn label(self, label: &'static str) -> Self {
+        self.entry.labels.push(label);
+        self
+    }
+
+    /// Execute the system before every system which has the label.
+    pub fn before(self, label: &'static str) -> Self {
+        self.entry.before.push(label);
+        self
+    }
+
+    /// Execute the system after every system which has the label.
+    pub fn after(self, label: &'static str) -> Self {
+        self.entry.after.push(label);
+        self
+    }
+}
+
+fn system_name<T>(_: &T) -> &'static str {
+    type_name::<T>()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecs::{Commands, Query, ResMut};
+
+    fn push(schedule: &mut Schedule, value: i32) -> SystemConfig<'_> {
+        schedule.add_system(move |mut log: ResMut<'_, Vec<i32>>| log.push(value))
+    }
+
+    fn run(schedule: &mut Schedule) -> Vec<i32> {
+        let mut world = World::builder().build();
+        world.insert_resource(Vec::<i32>::new());
+        schedule.run(&mut world);
+        world.remove_resource::<Vec<i32>>().unwrap()
+    }
+
+    #[test]
+    fn insertion_order() {
+        let mut schedule = Schedule::new();
+        push(&mut schedule, 0);
+        push(&mut schedule, 1);
+        push(&mut schedule, 2);
+        assert_eq!(run(&mut schedule), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn before_and_after() {
+        let mut schedule = Schedule::new();
+        push(&mut schedule, 0).after("b");
+        push(&mut schedule, 1).label("b");
+        push(&mut schedule, 2).label("c").before("b");
+        assert_eq!(run(&mut schedule), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn shared_label() {
+        let mut schedule = Schedule::new();
+        push(&mut schedule, 0).after("input");
+        push(&mut schedule, 1).label("input");
+        push(&mut schedule, 2).label("input");
+        assert_eq!(run(&mut schedule), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn cycle() {
+        let mut schedule = Schedule::new();
+        push(&mut schedule, 0);
+        push(&mut schedule, 1).label("a").after("b");
+        push(&mut schedule, 2).label("b").after("a");
+        let mut world = World::builder().build();
+        assert!(matches!(
+            schedule.try_run(&mut world),
+            Err(Error::CyclicDependency(names)) if names.len() == 2
+        ));
+    }
+
+    #[test]
+    fn unknown_label() {
+        let mut schedule = Schedule::new();
+        push(&mut schedule, 0).after("missing");
+        let mut world = World::builder().build();
+        assert_eq!(
+            schedule.try_run(&mut world),
+            Err(Error::UnknownLabel("missing"))
+        );
+    }
+
+    #[test]
+    fn systems_keep_state() {
+        let mut schedule = Schedule::new();
+        let mut count = 0;
+        schedule.add_system(move |mut log: ResMut<'_, Vec<i32>>| {
+            count += 1;
+            log.push(count);
+        });
+        let mut world = World::builder().build();
+        world.insert_resource(Vec::<i32>::new());
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(*world.get_resource::<Vec<i32>>().unwrap(), vec![1, 2]);
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    #[test]
+    fn non_thread_safe_systems() {
+        use std::{cell::Cell, rc::Rc};
+
+        let mut schedule = Schedule::new();
+        let count = Rc::new(Cell::new(0));
+        let cloned = Rc::clone(&count);
+        schedule.add_system(move || cloned.set(cloned.get() + 1));
+        let mut world = World::builder().build();
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn commands_are_applied_between_systems() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(|mut commands: Commands<'_>| {
+            commands.spawn().attach(1);
+        });
+        schedule.add_system(|query: Query<'_, &i32>, mut log: ResMut<'_, Vec<i32>>| {
+            log.push(query.iter().count() as i32);
+        });
+        let mut world = World::builder().register_component::<i32>().build();
+        world.insert_resource(Vec::<i32>::new());
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(*world.get_resource::<Vec<i32>>().unwrap(), vec![1, 2]);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_batches() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(|_: Query<'_, &mut i32>| {});
+        schedule.add_system(|_: Query<'_, &f32>| {});
+        schedule.add_system(|_: Query<'_, &i32>, _: Query<'_, &f32>| {});
+        schedule
+            .add_system(|_: Query<'_, &mut f32>| {})
+            .label("write");
+        schedule
+            .add_system(|_: ResMut<'_, Vec<i32>>| {})
+            .before("write");
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<f32>()
+            .build();
+        world.insert_resource(Vec::<i32>::new());
+        schedule.run_parallel(&mut world);
+        let plan = schedule.plan.as_ref().unwrap();
+        assert_eq!(plan.order, vec![0, 1, 2, 4, 3]);
+        assert_eq!(plan.batches, vec![vec![0, 1, 4], vec![2], vec![3]]);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn run_parallel() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(|mut query: Query<'_, &mut i32>| {
+            for mut value in query.iter_mut() {
+                *value += 1;
+            }
+        });
+        schedule.add_system(|mut query: Query<'_, &mut f32>| {
+            for mut value in query.iter_mut() {
+                *value += 1.0;
+            }
+        });
+        schedule.add_system(|mut commands: Commands<'_>, query: Query<'_, &i32>| {
+            for value in query.iter() {
+                commands.insert_resource(*value);
+            }
+        });
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<f32>()
+            .build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 0);
+        world.attach_component(entity, 0.0f32);
+        for _ in 0..3 {
+            schedule.run_parallel(&mut world);
+        }
+        assert_eq!(world.get_component::<i32>(entity).as_deref(), Some(&3));
+        assert_eq!(world.get_component::<f32>(entity).as_deref(), Some(&3.0));
+        assert_eq!(world.get_resource::<i32>().as_deref(), Some(&3));
+    }
+}
@@ -0,0 +1,771 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+};
+
+use super::{
+    archetype::EntityLocation,
+    change::{ComponentTicks, Mut, Tick},
+    Archetype, Component, Entity, MaybeSendSync,
+};
+use crate::{
+    collections::{AtomicRef, AtomicRefCell, AtomicRefMut, GenerationalSparseVec},
+    Error,
+};
+
+/// How the components of a type are stored
+///
+/// The storage kind is chosen per component type with
+/// [`WorldBuilder::register_component_with_storage`](crate::ecs::WorldBuilder::register_component_with_storage).
+/// Components stored in different kinds can be used in the same query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageKind {
+    /// Components are stored in a vector indexed by the entity index.
+    ///
+    /// Attaching and detaching components is cheap, but iteration visits every slot up to the
+    /// largest entity index which has the component.
+    #[default]
+    SparseVec,
+    /// Components are packed in a dense vector, with a sparse array which maps the entity index to
+    /// the position in it.
+    ///
+    /// Attaching and detaching components is cheap, and queries which require sparse-set
+    /// components only visit the entities which have them. This suits rare components.
+    SparseSet,
+    /// Components are stored in the tables of archetypes, which group entities by their set of
+    /// table components.
+    ///
+    /// Queries which require table components only visit the entities in the matching archetypes,
+    /// and read the components contiguously. Attaching or detaching a table component moves the
+    /// other table components of the entity to another archetype.
+    Table,
+}
+
+/// Columns of the archetype tables for a component type
+///
+/// `columns[archetype]` is empty if the archetype does not have the component.
+pub struct Table<T> {
+    columns: Vec<Vec<T>>,
+}
+
+impl<T> Table<T> {
+    const fn new() -> Self {
+        Self {
+            columns: Vec::new(),
+        }
+    }
+
+    pub(crate) fn get(&self, location: EntityLocation) -> Option<&T> {
+        self.columns.get(location.archetype)?.get(location.row)
+    }
+
+    pub(crate) fn get_mut(&mut self, location: EntityLocation) -> Option<&mut T> {
+        self.columns
+            .get_mut(location.archetype)?
+            .get_mut(location.row)
+    }
+
+    fn reserve(&mut self, archetype: usize, additional: usize) {
+        if self.columns.len() <= archetype {
+            self.columns.resize_with(archetype + 1, Vec::new);
+        }
+        self.columns[archetype].reserve(additional);
+    }
+
+    /// Add a component to the end of the column of the archetype.
+    pub(crate) fn push(&mut self, archetype: usize, value: T) {
+        if self.columns.len() <= archetype {
+            self.columns.resize_with(archetype + 1, Vec::new);
+        }
+        self.columns[archetype].push(value);
+    }
+
+    pub(crate) fn swap_remove(&mut self, location: EntityLocation) -> T {
+        self.columns[location.archetype].swap_remove(location.row)
+    }
+}
+
+/// Packed storage of the components of a type
+///
+/// `sparse[index]` is the position of the component of the entity in `dense` and `entities`.
+pub struct SparseSet<T> {
+    sparse: Vec<Option<usize>>,
+    dense: Vec<T>,
+    entities: Vec<Entity>,
+}
+
+impl<T> SparseSet<T> {
+    const fn new() -> Self {
+        Self {
+            sparse: Vec::new(),
+            dense: Vec::new(),
+            entities: Vec::new(),
+        }
+    }
+
+    fn reserve(&mut self, index_len: usize, additional: usize) {
+        self.sparse
+            .reserve(index_len.saturating_sub(self.sparse.len()));
+        self.dense.reserve(additional);
+        self.entities.reserve(additional);
+    }
+
+    fn position(&self, index: usize) -> Option<usize> {
+        self.sparse.get(index).copied().flatten()
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<&T> {
+        self.dense.get(self.position(index)?)
+    }
+
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let position = self.position(index)?;
+        self.dense.get_mut(position)
+    }
+
+    /// Returns the entities which have the component, in the order of `dense`.
+    pub(crate) fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Insert the component of the alive entity, returning the previous one if any.
+    pub(crate) fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        if let Some(position) = self.position(entity.index) {
+            self.entities[position] = entity;
+            return Some(std::mem::replace(&mut self.dense[position], value));
+        }
+        if self.sparse.len() <= entity.index {
+            self.sparse.resize(entity.index + 1, None);
+        }
+        self.sparse[entity.index] = Some(self.dense.len());
+        self.dense.push(value);
+        self.entities.push(entity);
+        None
+    }
+
+    /// Remove the component of the entity at the index.
+    pub(crate) fn remove(&mut self, index: usize) -> Option<T> {
+        let position = self.sparse.get_mut(index)?.take()?;
+        let value = self.dense.swap_remove(position);
+        self.entities.swap_remove(position);
+        // 末尾から移動してきた要素の位置を更新する
+        if let Some(moved) = self.entities.get(position) {
+            self.sparse[moved.index] = Some(position);
+        }
+        Some(value)
+    }
+}
+
+/// Component with the ticks when it was attached and last mutated
+pub struct Ticked<T> {
+    pub(crate) value: T,
+    pub(crate) ticks: ComponentTicks,
+}
+
+impl<T> Ticked<T> {
+    /// Wrap a component attached at the tick.
+    pub(crate) const fn new(value: T, tick: Tick) -> Self {
+        Self {
+            value,
+            ticks: ComponentTicks::new(tick),
+        }
+    }
+
+    /// Returns a mutable reference which marks the component changed at `this_run`.
+    pub(crate) const fn as_mut(&mut self, this_run: Tick) -> Mut<'_, T> {
+        Mut {
+            value: &mut self.value,
+            ticks: &mut self.ticks,
+            this_run,
+        }
+    }
+}
+
+/// Storage of the components of a type
+pub enum Storage<T> {
+    SparseVec(GenerationalSparseVec<Ticked<T>>),
+    SparseSet(SparseSet<Ticked<T>>),
+    Table(Table<Ticked<T>>),
+}
+
+impl<T: Component> Storage<T> {
+    /// Returns the upper bound of the entity indices which have the component, or None for tables.
+    pub(crate) fn sparse_len(&self) -> Option<usize> {
+        match self {
+            Self::SparseVec(vec) => Some(vec.data_slice().len()),
+            Self::SparseSet(set) => Some(set.sparse.len()),
+            Self::Table(_) => None,
+        }
+    }
+
+    /// Returns the entities which have the component if it is stored in a sparse set.
+    pub(crate) fn packed_entities(&self) -> Option<&[Entity]> {
+        match self {
+            Self::SparseSet(set) => Some(set.entities()),
+            _ => None,
+        }
+    }
+
+    pub(crate) const fn is_table(&self) -> bool {
+        matches!(self, Self::Table(_))
+    }
+
+    /// Check if the entities in the archetype may have the component.
+    pub(crate) fn matches_archetype(&self, archetype: &Archetype) -> bool {
+        match self {
+            Self::SparseVec(_) | Self::SparseSet(_) => true,
+            Self::Table(_) => archetype.contains::<T>(),
+        }
+    }
+}
+
+impl<T> Storage<T> {
+    const fn new(kind: StorageKind) -> Self {
+        match kind {
+            StorageKind::SparseVec => Self::SparseVec(GenerationalSparseVec::new()),
+            StorageKind::SparseSet => Self::SparseSet(SparseSet::new()),
+            StorageKind::Table => Self::Table(Table::new()),
+        }
+    }
+
+    /// Get the component of the alive entity at the location.
+    pub(crate) fn get(&self, location: EntityLocation) -> Option<&T> {
+        self.get_ticked(location).map(|ticked| &ticked.value)
+    }
+
+    /// Get the component of the alive entity at the location with its ticks.
+    pub(crate) fn get_ticked(&self, location: EntityLocation) -> Option<&Ticked<T>> {
+        match self {
+            Self::SparseVec(vec) => vec.get(location.entity()),
+            Self::SparseSet(set) => set.get(location.index),
+            Self::Table(table) => table.get(location),
+        }
+    }
+
+    /// Get the component of the alive entity at the location with its ticks.
+    pub(crate) fn get_ticked_mut(&mut self, location: EntityLocation) -> Option<&mut Ticked<T>> {
+        match self {
+            Self::SparseVec(vec) => vec.get_mut(location.entity()),
+            Self::SparseSet(set) => set.get_mut(location.index),
+            Self::Table(table) => table.get_mut(location),
+        }
+    }
+}
+
+/// Raw pointers to the components in a storage
+///
+/// Queries take them while they borrow the storage, and reach the component of each entity through
+/// them, so that the items of different entities can be alive at the same time. The pointers stay
+/// valid until the storage is changed structurally, which requires `&mut World`.
+pub enum StoragePtr<T> {
+    SparseVec {
+        ptr: *mut Option<T>,
+        // ptr[i] を所有しているエンティティの世代
+        generations: *const u32,
+        len: usize,
+    },
+    SparseSet {
+        sparse: *const Option<usize>,
+        sparse_len: usize,
+        dense: *mut T,
+        entities: *const Entity,
+        len: usize,
+    },
+    // アーキタイプごとの列の先頭と長さ
+    Table {
+        columns: Vec<(*mut T, usize)>,
+    },
+}
+
+impl<T> Clone for StoragePtr<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::SparseVec {
+                ptr,
+                generations,
+                len,
+            } => Self::SparseVec {
+                ptr: *ptr,
+                generations: *generations,
+                len: *len,
+            },
+            Self::SparseSet {
+                sparse,
+                sparse_len,
+                dense,
+                entities,
+                len,
+            } => Self::SparseSet {
+                sparse: *sparse,
+                sparse_len: *sparse_len,
+                dense: *dense,
+                entities: *entities,
+                len: *len,
+            },
+            Self::Table { columns } => Self::Table {
+                columns: columns.clone(),
+            },
+        }
+    }
+}
+
+// SAFETY:
+// ポインタを参照外しするのは unsafe な関数だけで、その呼び出し側が同じ要素への可変参照を同時に作らないことを
+// 保証するので、異なるスレッドが異なる要素の可変参照を持つだけになる
+unsafe impl<T: Send> Send for StoragePtr<T> {}
+unsafe impl<T: Send + Sync> Sync for StoragePtr<T> {}
+
+impl<T> StoragePtr<T> {
+    /// Returns the upper bound of the entity indices which have the component, or None for tables.
+    pub(crate) const fn sparse_len(&self) -> Option<usize> {
+        match self {
+            Self::SparseVec { len, .. } => Some(*len),
+            Self::SparseSet { sparse_len, .. } => Some(*sparse_len),
+            Self::Table { .. } => None,
+        }
+    }
+
+    /// Returns the entities which have the component if it is stored in a sparse set.
+    pub(crate) const fn packed_entities(&self) -> Option<&[Entity]> {
+        match self {
+            // SAFETY: ストレージが構造的に変更されない間は、エンティティの並びも変わらない
+            Self::SparseSet { entities, len, .. } => {
+                Some(unsafe { std::slice::from_raw_parts(*entities, *len) })
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) const fn is_table(&self) -> bool {
+        matches!(self, Self::Table { .. })
+    }
+
+    /// Get the component of the alive entity at the location, if any.
+    ///
+    /// # Safety
+    ///
+    /// The storage must not have been changed structurally since the pointers were taken, and no
+    /// mutable reference to the component at the location may be alive while the returned
+    /// reference is alive.
+    pub(crate) unsafe fn get<'a>(&self, location: EntityLocation) -> Option<&'a T> {
+        // SAFETY: 呼び出し側の保証をそのまま引き継ぐ
+        match unsafe { self.slot(location) }? {
+            Slot::Option(ptr) => unsafe { (*ptr).as_ref() },
+            Slot::Value(ptr) => Some(unsafe { &*ptr }),
+        }
+    }
+
+    /// Get the component of the alive entity at the location mutably, if any.
+    ///
+    /// # Safety
+    ///
+    /// The pointers must have been taken by [`Storage::as_mut_ptr`], the storage must not have
+    /// been changed structurally since then, and no other reference to the component at the
+    /// location may be alive while the returned reference is alive.
+    pub(crate) unsafe fn get_mut<'a>(&self, location: EntityLocation) -> Option<&'a mut T> {
+        // SAFETY: 呼び出し側の保証をそのまま引き継ぐ
+        match unsafe { self.slot(location) }? {
+            Slot::Option(ptr) => unsafe { (*ptr).as_mut() },
+            Slot::Value(ptr) => Some(unsafe { &mut *ptr }),
+        }
+    }
+
+    /// 位置にある要素へのポインタを返す。範囲外なら None を返す
+    unsafe fn slot(&self, location: EntityLocation) -> Option<Slot<T>> {
+        // SAFETY:
+        // 範囲内であることを確認している。ストレージは変更されていないので、ポインタは有効な要素を指している
+        match self {
+            Self::SparseVec {
+                ptr,
+                generations,
+                len,
+            } => {
+                if location.index >= *len
+                    || unsafe { *generations.add(location.index) } != location.generation
+                {
+                    return None;
+                }
+                Some(Slot::Option(unsafe { ptr.add(location.index) }))
+            }
+            Self::SparseSet {
+                sparse,
+                sparse_len,
+                dense,
+                ..
+            } => {
+                if location.index >= *sparse_len {
+                    return None;
+                }
+                let position = unsafe { *sparse.add(location.index) }?;
+                Some(Slot::Value(unsafe { dense.add(position) }))
+            }
+            Self::Table { columns } => {
+                let &(ptr, len) = columns.get(location.archetype)?;
+                if location.row >= len {
+                    return None;
+                }
+                Some(Slot::Value(unsafe { ptr.add(location.row) }))
+            }
+        }
+    }
+}
+
+enum Slot<T> {
+    Option(*mut Option<T>),
+    Value(*mut T),
+}
+
+impl<T: Component> Storage<T> {
+    /// Take the pointers to the components, which may be written through.
+    pub(crate) fn as_mut_ptr(&mut self) -> StoragePtr<Ticked<T>> {
+        match self {
+            Self::SparseVec(vec) => {
+                let generations = vec.generations_slice();
+                let (generations, len) = (generations.as_ptr(), generations.len());
+                let slice = vec.data_mut_slice();
+                StoragePtr::SparseVec {
+                    ptr: slice.as_mut_ptr(),
+                    generations,
+                    len: len.min(slice.len()),
+                }
+            }
+            Self::SparseSet(set) => StoragePtr::SparseSet {
+                sparse: set.sparse.as_ptr(),
+                sparse_len: set.sparse.len(),
+                dense: set.dense.as_mut_ptr(),
+                entities: set.entities.as_ptr(),
+                len: set.entities.len(),
+            },
+            Self::Table(table) => StoragePtr::Table {
+                columns: table
+                    .columns
+                    .iter_mut()
+                    .map(|column| (column.as_mut_ptr(), column.len()))
+                    .collect(),
+            },
+        }
+    }
+
+    /// Take the pointers to the components, which must not be written through.
+    pub(crate) fn as_ptr(&self) -> StoragePtr<Ticked<T>> {
+        // 読み取り専用として扱うので、*mut にキャストしても書き込まれることはない
+        match self {
+            Self::SparseVec(vec) => {
+                let slice = vec.data_slice();
+                let generations = vec.generations_slice();
+                StoragePtr::SparseVec {
+                    ptr: slice.as_ptr().cast_mut(),
+                    generations: generations.as_ptr(),
+                    len: generations.len().min(slice.len()),
+                }
+            }
+            Self::SparseSet(set) => StoragePtr::SparseSet {
+                sparse: set.sparse.as_ptr(),
+                sparse_len: set.sparse.len(),
+                dense: set.dense.as_ptr().cast_mut(),
+                entities: set.entities.as_ptr(),
+                len: set.entities.len(),
+            },
+            Self::Table(table) => StoragePtr::Table {
+                columns: table
+                    .columns
+                    .iter()
+                    .map(|column| (column.as_ptr().cast_mut(), column.len()))
+                    .collect(),
+            },
+        }
+    }
+}
+
+// 型を知らなくても行える操作
+trait AnyStorage: MaybeSendSync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// エンティティのコンポーネントを取り除き、取り除いたかどうかを返す。テーブルの場合は何もしない
+    fn remove_sparse(&mut self, index: usize) -> bool;
+    /// エンティティがコンポーネントを持っているかどうか
+    fn contains(&self, location: EntityLocation) -> bool;
+    /// テーブルの行を取り除く。疎な場合は何もしない
+    fn swap_remove_row(&mut self, location: EntityLocation);
+    /// テーブルの行を別のアーキタイプの末尾に移す。疎な場合は何もしない
+    fn move_row(&mut self, location: EntityLocation, archetype: usize);
+    /// インデックスが index_len 未満のエンティティ、またはアーキタイプの行を additional 個追加できるように領域を確保する
+    fn reserve(&mut self, index_len: usize, archetype: usize, additional: usize);
+    fn is_table(&self) -> bool;
+}
+
+impl<T: Component> AnyStorage for Storage<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove_sparse(&mut self, index: usize) -> bool {
+        match self {
+            Self::SparseVec(vec) => vec.remove_index(index).is_some(),
+            Self::SparseSet(set) => set.remove(index).is_some(),
+            Self::Table(_) => false,
+        }
+    }
+
+    fn contains(&self, location: EntityLocation) -> bool {
+        self.get_ticked(location).is_some()
+    }
+
+    fn swap_remove_row(&mut self, location: EntityLocation) {
+        if let Self::Table(table) = self {
+            table.swap_remove(location);
+        }
+    }
+
+    fn move_row(&mut self, location: EntityLocation, archetype: usize) {
+        if let Self::Table(table) = self {
+            let value = table.swap_remove(location);
+            table.push(archetype, value);
+        }
+    }
+
+    fn reserve(&mut self, index_len: usize, archetype: usize, additional: usize) {
+        match self {
+            Self::SparseVec(vec) => vec.reserve_indices(index_len),
+            Self::SparseSet(set) => set.reserve(index_len, additional),
+            Self::Table(table) => table.reserve(archetype, additional),
+        }
+    }
+
+    fn is_table(&self) -> bool {
+        Self::is_table(self)
+    }
+}
+
+pub struct Components {
+    map: HashMap<TypeId, AtomicRefCell<Box<dyn AnyStorage>>>,
+}
+
+impl Components {
+    pub(crate) fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn register<T: Component>(&mut self, kind: StorageKind) {
+        self.map.insert(
+            TypeId::of::<T>(),
+            AtomicRefCell::new(Box::new(Storage::<T>::new(kind))),
+        );
+    }
+
+    pub(crate) fn contains_id(&self, type_id: TypeId) -> bool {
+        self.map.contains_key(&type_id)
+    }
+
+    pub(crate) fn is_table_id(&mut self, type_id: TypeId) -> bool {
+        self.map
+            .get_mut(&type_id)
+            .is_some_and(|storage| storage.get_mut().is_table())
+    }
+
+    /// Check if the entity at the location has the component of the type.
+    pub(crate) fn contains_at(&mut self, type_id: TypeId, location: EntityLocation) -> bool {
+        self.map
+            .get_mut(&type_id)
+            .is_some_and(|storage| storage.get_mut().contains(location))
+    }
+
+    /// Reserve capacity for `additional` entities whose indices are less than `index_len` and
+    /// whose table components are in `archetype`.
+    pub(crate) fn reserve(
+        &mut self,
+        types: impl IntoIterator<Item = TypeId>,
+        index_len: usize,
+        archetype: usize,
+        additional: usize,
+    ) {
+        for type_id in types {
+            if let Some(storage) = self.map.get_mut(&type_id) {
+                storage.get_mut().reserve(index_len, archetype, additional);
+            }
+        }
+    }
+
+    /// Remove the components of the entity from every sparse storage, calling `on_removed` with
+    /// the type of each removed component.
+    pub(crate) fn remove_sparse(&mut self, index: usize, mut on_removed: impl FnMut(TypeId)) {
+        for (&type_id, storage) in &mut self.map {
+            if storage.get_mut().remove_sparse(index) {
+                on_removed(type_id);
+            }
+        }
+    }
+
+    /// Remove the row from the tables of the types.
+    pub(crate) fn swap_remove_row(&mut self, types: &[TypeId], location: EntityLocation) {
+        for type_id in types {
+            if let Some(storage) = self.map.get_mut(type_id) {
+                storage.get_mut().swap_remove_row(location);
+            }
+        }
+    }
+
+    /// Move the row from the tables of the types to another archetype.
+    pub(crate) fn move_row<'a>(
+        &mut self,
+        types: impl IntoIterator<Item = &'a TypeId>,
+        location: EntityLocation,
+        archetype: usize,
+    ) {
+        for type_id in types {
+            if let Some(storage) = self.map.get_mut(type_id) {
+                storage.get_mut().move_row(location, archetype);
+            }
+        }
+    }
+
+    pub(crate) fn get_mut<T: Component>(&mut self) -> Result<&mut Storage<T>, Error> {
+        let storage = self
+            .map
+            .get_mut(&TypeId::of::<T>())
+            .ok_or_else(unregistered::<T>)?;
+        // SAFETY:
+        // self.map[TypeId::of<T>] には Storage<T> が登録されているので、ダウンキャストは必ず成功する
+        Ok(unsafe {
+            storage
+                .get_mut()
+                .as_any_mut()
+                .downcast_mut()
+                .unwrap_unchecked()
+        })
+    }
+
+    pub(crate) fn borrow<T: Component>(&self) -> Result<AtomicRef<'_, Storage<T>>, Error> {
+        let storage = self
+            .map
+            .get(&TypeId::of::<T>())
+            .ok_or_else(unregistered::<T>)?;
+        let borrow = storage.try_borrow().map_err(|_| borrow_conflict::<T>())?;
+        Ok(AtomicRef::map(borrow, |storage| {
+            // SAFETY:
+            // self.map[TypeId::of<T>] には Storage<T> が登録されているので、ダウンキャストは必ず成功する
+            unsafe { storage.as_any().downcast_ref().unwrap_unchecked() }
+        }))
+    }
+
+    pub(crate) fn borrow_mut<T: Component>(&self) -> Result<AtomicRefMut<'_, Storage<T>>, Error> {
+        let storage = self
+            .map
+            .get(&TypeId::of::<T>())
+            .ok_or_else(unregistered::<T>)?;
+        let borrow = storage
+            .try_borrow_mut()
+            .map_err(|_| borrow_conflict::<T>())?;
+        Ok(AtomicRefMut::map(borrow, |storage| {
+            // SAFETY:
+            // self.map[TypeId::of<T>] には Storage<T> が登録されているので、ダウンキャストは必ず成功する
+            unsafe { storage.as_any_mut().downcast_mut().unwrap_unchecked() }
+        }))
+    }
+}
+
+fn unregistered<T: Component>() -> Error {
+    Error::UnregisteredComponent(type_name::<T>())
+}
+
+fn borrow_conflict<T: Component>() -> Error {
+    Error::BorrowConflict(type_name::<T>())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn table_columns() {
+        let mut table = Table::new();
+        table.push(1, 'a');
+        table.push(1, 'b');
+        table.push(3, 'c');
+        let location = |archetype, row| EntityLocation {
+            index: 0,
+            generation: 0,
+            archetype,
+            row,
+        };
+        assert_eq!(table.get(location(1, 1)), Some(&'b'));
+        assert_eq!(table.get(location(0, 0)), None);
+        assert_eq!(table.get(location(3, 1)), None);
+        assert_eq!(table.swap_remove(location(1, 0)), 'a');
+        assert_eq!(table.get(location(1, 0)), Some(&'b'));
+    }
+
+    #[test]
+    fn sparse_vec_generation() {
+        let location = |generation| EntityLocation {
+            index: 2,
+            generation,
+            archetype: 0,
+            row: 0,
+        };
+        let mut storage = Storage::new(StorageKind::SparseVec);
+        if let Storage::SparseVec(vec) = &mut storage {
+            assert!(vec
+                .insert(location(1).entity(), Ticked::new('a', Tick::default()))
+                .is_ok());
+        }
+        assert_eq!(storage.get(location(1)), Some(&'a'));
+        // 同じインデックスの古いエンティティからは読めない
+        assert_eq!(storage.get(location(0)), None);
+        assert!(storage.get_ticked_mut(location(0)).is_none());
+
+        let ptr = storage.as_mut_ptr();
+        // SAFETY: ストレージは変更しておらず、同時に他の参照を作らない
+        unsafe {
+            assert_eq!(ptr.get(location(1)).map(|ticked| ticked.value), Some('a'));
+            assert!(ptr.get(location(0)).is_none());
+            assert!(ptr.get_mut(location(0)).is_none());
+        }
+    }
+
+    #[test]
+    fn sparse_set() {
+        let entity = |index| Entity {
+            index,
+            generation: 0,
+        };
+        let mut set = SparseSet::new();
+        assert_eq!(set.insert(entity(3), 'a'), None);
+        assert_eq!(set.insert(entity(1), 'b'), None);
+        assert_eq!(set.insert(entity(5), 'c'), None);
+        assert_eq!(set.insert(entity(1), 'd'), Some('b'));
+        assert_eq!(set.get(1), Some(&'d'));
+        assert_eq!(set.get(2), None);
+        assert_eq!(set.get(8), None);
+
+        assert_eq!(set.remove(3), Some('a'));
+        assert_eq!(set.remove(3), None);
+        assert_eq!(set.entities(), [entity(5), entity(1)]);
+        assert_eq!(set.get(5), Some(&'c'));
+        *set.get_mut(5).unwrap() = 'e';
+        assert_eq!(set.dense, ['e', 'd']);
+    }
+
+    #[test]
+    fn borrow() {
+        let mut components = Components::new();
+        components.register::<i32>(StorageKind::Table);
+        assert_eq!(
+            components.borrow::<f32>().err().map(|err| err.to_string()),
+            Some("component `f32` is not registered".to_string())
+        );
+        let shared = components.borrow::<i32>().unwrap();
+        assert!(shared.is_table());
+        assert!(components.borrow::<i32>().is_ok());
+        assert_eq!(
+            components.borrow_mut::<i32>().err(),
+            Some(Error::BorrowConflict("i32"))
+        );
+    }
+}
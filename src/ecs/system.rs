@@ -1,30 +1,210 @@
-use super::{FromWorld, World};
+use super::{Access, SystemTicks, Tick, World};
+use crate::Error;
+
+/// Represents a type which can be taken from a world as a parameter of a system.
+///
+/// This trait is implemented for [`Query`](crate::ecs::Query), and for tuples of up to 16
+/// parameters.
+pub trait FromWorld {
+    /// The type passed to the system, which borrows the world for `'world`
+    ///
+    /// This is usually `Self` with its lifetime replaced by `'world`.
+    type Item<'world>;
+
+    /// Declare the components which the parameter reads or writes.
+    fn access(access: &mut Access);
+
+    /// Take the value from the world for a run of a system.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if [`FromWorld::try_from_world`] returns an error.
+    fn from_world(world: &World, ticks: SystemTicks) -> Self::Item<'_> {
+        Self::try_from_world(world, ticks).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Take the value from the world for a run of a system, or return an error if the components
+    /// it needs are not registered or already borrowed.
+    ///
+    /// `ticks` is used to detect the components which were attached or mutated since the last run
+    /// of the system.
+    fn try_from_world(world: &World, ticks: SystemTicks) -> Result<Self::Item<'_>, Error>;
+}
+
+impl FromWorld for () {
+    type Item<'world> = ();
+
+    fn access(_access: &mut Access) {}
+
+    fn try_from_world(_world: &World, _ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+        Ok(())
+    }
+}
+
+macro_rules! impl_from_world_for_tuple {
+    ($($name:ident),*) => {
+        impl<$($name: FromWorld),*> FromWorld for ($($name,)*) {
+            type Item<'world> = ($($name::Item<'world>,)*);
+
+            fn access(access: &mut Access) {
+                $({
+                    let mut param = Access::new();
+                    $name::access(&mut param);
+                    access.extend(&param);
+                })*
+            }
+
+            /// Take the parameters from the world, or return [`Error::ConflictingAccess`] if two of
+            /// them alias mutably.
+            fn try_from_world(world: &World, ticks: SystemTicks) -> Result<Self::Item<'_>, Error> {
+                let mut access = Access::new();
+                Self::access(&mut access);
+                access.check()?;
+                Ok(($($name::try_from_world(world, ticks)?,)*))
+            }
+        }
+    };
+}
+
+all_tuples!(
+    impl_from_world_for_tuple,
+    P0,
+    P1,
+    P2,
+    P3,
+    P4,
+    P5,
+    P6,
+    P7,
+    P8,
+    P9,
+    P10,
+    P11,
+    P12,
+    P13,
+    P14,
+    P15
+);
 
 mod private {
-    pub trait Sealed<'world, T> {}
+    pub trait Sealed<T> {}
 }
 
 /// Represents a system that can be executed on a world.
 ///
+/// This trait is implemented for functions and closures which take up to 16 parameters
+/// implementing [`FromWorld`]. Systems are not consumed by execution, so the same system can be
+/// executed repeatedly, e.g. by a [`Schedule`](crate::ecs::Schedule). The parameters must not
+/// alias each other mutably, e.g. a system cannot take both `Query<'_, &Position>` and
+/// `Query<'_, &mut Position>`.
+///
 /// This trait is not meant to be implemented by the user. See Implementors section for types that
 /// can be used as systems.
-pub trait System<'world, T>: private::Sealed<'world, T> {
-    fn execute(self, world: &'world mut World);
-}
+pub trait System<T>: private::Sealed<T> {
+    /// Execute the system, and apply the commands recorded by it.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the parameters of the system cannot be taken from the world.
+    fn execute(&mut self, world: &mut World);
+
+    /// Execute the system and apply the commands recorded by it, or return an error if the
+    /// parameters of the system cannot be taken from the world.
+    fn try_execute(&mut self, world: &mut World) -> Result<(), Error>;
+
+    /// Execute the system without applying the commands recorded by it, or return an error if the
+    /// parameters of the system cannot be taken from the world.
+    ///
+    /// The commands are applied by the next [`World::flush`]. Since this only needs a shared
+    /// reference to the world, systems whose [`System::access`] don't conflict can be run at the
+    /// same time on different threads.
+    ///
+    /// The system is executed as if it has never run, so every component is regarded as added and
+    /// changed. Use [`System::try_run_since`] to detect the changes since the previous run.
+    fn try_run(&mut self, world: &World) -> Result<(), Error>;
+
+    /// Execute the system without applying the commands recorded by it, regarding the components
+    /// attached or mutated after `last_run` as added or changed.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the tick of this run, which should be passed as `last_run` to the next run.
+    /// Returns an error if the parameters of the system cannot be taken from the world.
+    fn try_run_since(&mut self, world: &World, last_run: Tick) -> Result<Tick, Error>;
 
-impl<'world, T, F> private::Sealed<'world, T> for F
-where
-    T: FromWorld<'world>,
-    F: FnOnce(T),
-{
+    /// Returns the components and resources which the parameters of the system read or write.
+    fn access(&self) -> Access;
 }
 
-impl<'world, T, F> System<'world, T> for F
-where
-    T: FromWorld<'world>,
-    F: FnOnce(T),
-{
-    fn execute(self, world: &'world mut World) {
-        self(T::from_world(world));
-    }
+macro_rules! impl_system {
+    ($($name:ident),*) => {
+        impl<Func, $($name: FromWorld),*> private::Sealed<($($name,)*)> for Func
+        where
+            Func: FnMut($($name),*) + FnMut($($name::Item<'_>),*),
+        {
+        }
+
+        #[allow(non_snake_case)]
+        impl<Func, $($name: FromWorld),*> System<($($name,)*)> for Func
+        where
+            Func: FnMut($($name),*) + FnMut($($name::Item<'_>),*),
+        {
+            fn execute(&mut self, world: &mut World) {
+                self.try_execute(world).unwrap_or_else(|err| panic!("{}", err))
+            }
+
+            fn try_execute(&mut self, world: &mut World) -> Result<(), Error> {
+                self.try_run(world)?;
+                world.flush();
+                Ok(())
+            }
+
+            fn try_run(&mut self, world: &World) -> Result<(), Error> {
+                self.try_run_since(world, Tick::default())?;
+                Ok(())
+            }
+
+            fn try_run_since(&mut self, world: &World, last_run: Tick) -> Result<Tick, Error> {
+                // 引数の型を推論させるために、一度ジェネリックな関数を経由して呼び出す
+                #[allow(clippy::too_many_arguments)]
+                fn call_inner<$($name),*>(mut f: impl FnMut($($name),*), $($name: $name),*) {
+                    f($($name),*)
+                }
+                let ticks = SystemTicks {
+                    last_run,
+                    this_run: world.increment_change_tick(),
+                };
+                let ($($name,)*) = <($($name,)*) as FromWorld>::try_from_world(world, ticks)?;
+                call_inner(&mut *self, $($name),*);
+                Ok(ticks.this_run)
+            }
+
+            fn access(&self) -> Access {
+                let mut access = Access::new();
+                <($($name,)*) as FromWorld>::access(&mut access);
+                access
+            }
+        }
+    };
 }
+
+impl_system!();
+all_tuples!(
+    impl_system,
+    P0,
+    P1,
+    P2,
+    P3,
+    P4,
+    P5,
+    P6,
+    P7,
+    P8,
+    P9,
+    P10,
+    P11,
+    P12,
+    P13,
+    P14,
+    P15
+);
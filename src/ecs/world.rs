@@ -1,13 +1,31 @@
 use std::{
     any::TypeId,
-    borrow::BorrowMut,
-    cell::{Ref, RefCell, RefMut},
-    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, PoisonError,
+    },
 };
 
-use crate::collections::{GenerationalId, GenerationalVec, SparseVec, TypeErasedSparseVec};
+use crate::{
+    collections::{AtomicRef, GenerationalId, GenerationalVec},
+    Error,
+};
 
-use super::{Component, System};
+use super::{
+    archetype::{Archetypes, EntityLocation},
+    bundle::{Bundle, BundleReader, BundleWriter},
+    change::{Mut, Tick},
+    command::CommandQueue,
+    event::{Event, Events},
+    hierarchy::{self, Children, Hierarchy, Parent},
+    hook::{HookKind, Hooks},
+    observer::{Observers, Traversal},
+    relation::{Relation, RelationPolicy, Relations},
+    removal::Removals,
+    resource::Resources,
+    storage::{Components, Storage, StorageKind, Ticked},
+    Commands, Component, Entity, MaybeSendSync, Resource, System,
+};
 
 pub struct WorldBuilder {
     world: World,
@@ -20,8 +38,125 @@ impl WorldBuilder {
         }
     }
 
-    pub fn register_component<T: Component>(mut self) -> Self {
-        self.world.components.register::<T>();
+    /// Register a component type, which is stored in [`StorageKind::SparseVec`].
+    pub fn register_component<T: Component>(self) -> Self {
+        self.register_component_with_storage::<T>(StorageKind::default())
+    }
+
+    /// Register a component type, which is stored in the specified kind of storage.
+    pub fn register_component_with_storage<T: Component>(mut self, kind: StorageKind) -> Self {
+        self.world.components.register::<T>(kind);
+        self
+    }
+
+    /// Register a relation type with the cleanup policies applied when an entity is despawned.
+    pub fn register_relation<R: Relation>(mut self, policy: RelationPolicy) -> Self {
+        self.world.relations.register::<R>(policy);
+        self
+    }
+
+    /// Add a hook which is called after the component is attached to an entity which did not have
+    /// it.
+    ///
+    /// Hooks are called with the world, the entity, and [`Commands`] which are applied after the
+    /// change to the world has finished, so that they can keep invariants about the component.
+    /// With the `parallel` feature, they must be `Send` and `Sync`.
+    ///
+    /// ```
+    /// use xanadu::ecs::{Commands, Entity, World};
+    ///
+    /// struct RigidBody;
+    /// #[derive(Default)]
+    /// struct Physics {
+    ///     bodies: Vec<Entity>,
+    /// }
+    ///
+    /// let mut world = World::builder()
+    ///     .register_component::<RigidBody>()
+    ///     .on_add::<RigidBody>(|_, entity, commands| {
+    ///         commands.add(move |world| {
+    ///             world.get_resource_mut::<Physics>().unwrap().bodies.push(entity);
+    ///         });
+    ///     })
+    ///     .on_remove::<RigidBody>(|_, entity, commands| {
+    ///         commands.add(move |world| {
+    ///             let physics = world.get_resource_mut::<Physics>().unwrap();
+    ///             physics.bodies.retain(|&body| body != entity);
+    ///         });
+    ///     })
+    ///     .build();
+    /// world.insert_resource(Physics::default());
+    ///
+    /// let entity = world.new_entity();
+    /// world.attach_component(entity, RigidBody);
+    /// assert_eq!(world.get_resource::<Physics>().unwrap().bodies, [entity]);
+    /// world.despawn(entity);
+    /// assert!(world.get_resource::<Physics>().unwrap().bodies.is_empty());
+    /// ```
+    pub fn on_add<T: Component>(
+        self,
+        hook: impl Fn(&World, Entity, &mut Commands<'_>) + MaybeSendSync + 'static,
+    ) -> Self {
+        self.add_hook::<T>(HookKind::Add, hook)
+    }
+
+    /// Add a hook which is called after the component is attached to an entity, whether it
+    /// replaced another value or not.
+    ///
+    /// See [`WorldBuilder::on_add`] for the arguments of hooks.
+    pub fn on_insert<T: Component>(
+        self,
+        hook: impl Fn(&World, Entity, &mut Commands<'_>) + MaybeSendSync + 'static,
+    ) -> Self {
+        self.add_hook::<T>(HookKind::Insert, hook)
+    }
+
+    /// Add a hook which is called before the component of an entity is replaced with another
+    /// value, detached, or despawned with the entity.
+    ///
+    /// The hook can still read the old value from the world. See [`WorldBuilder::on_add`] for the
+    /// arguments of hooks.
+    pub fn on_replace<T: Component>(
+        self,
+        hook: impl Fn(&World, Entity, &mut Commands<'_>) + MaybeSendSync + 'static,
+    ) -> Self {
+        self.add_hook::<T>(HookKind::Replace, hook)
+    }
+
+    /// Add a hook which is called before the component is detached from an entity, or despawned
+    /// with the entity.
+    ///
+    /// The hook can still read the component from the world. When an entity is despawned, the hooks
+    /// of its components are called in the order their types were first given a hook. See
+    /// [`WorldBuilder::on_add`] for the arguments of hooks.
+    pub fn on_remove<T: Component>(
+        self,
+        hook: impl Fn(&World, Entity, &mut Commands<'_>) + MaybeSendSync + 'static,
+    ) -> Self {
+        self.add_hook::<T>(HookKind::Remove, hook)
+    }
+
+    fn add_hook<T: Component>(
+        mut self,
+        kind: HookKind,
+        hook: impl Fn(&World, Entity, &mut Commands<'_>) + MaybeSendSync + 'static,
+    ) -> Self {
+        self.world.hooks.add(
+            TypeId::of::<T>(),
+            kind,
+            contains_component::<T>,
+            Box::new(hook),
+        );
+        self
+    }
+
+    /// Add an event type, so that systems can send and read it with
+    /// [`EventWriter`](crate::ecs::EventWriter) and [`EventReader`](crate::ecs::EventReader).
+    pub fn add_event<E: Event>(mut self) -> Self {
+        if !self.world.has_resource::<Events<E>>() {
+            self.world.insert_resource(Events::<E>::new());
+            self.world.event_updaters.push(update_events::<E>);
+        }
         self
     }
 
@@ -37,15 +172,54 @@ impl Default for WorldBuilder {
 }
 
 pub struct World {
-    entities: GenerationalVec<()>,
+    pub(crate) entities: GenerationalVec<()>,
     pub(crate) components: Components,
+    pub(crate) archetypes: Archetypes,
+    pub(crate) resources: Resources,
+    pub(crate) command_queue: Mutex<CommandQueue>,
+    pub(crate) removals: Removals,
+    event_updaters: Vec<fn(&mut Self)>,
+    hooks: Hooks,
+    pub(crate) observers: Observers,
+    pub(crate) relations: Relations,
+    // reserve_entity で予約され、まだ実体化されていないエンティティの数
+    reserved_entities: AtomicUsize,
+    change_tick: AtomicU64,
 }
 
 impl World {
     pub(self) fn new() -> Self {
+        let mut components = Components::new();
+        components.register::<Parent>(StorageKind::default());
+        components.register::<Children>(StorageKind::default());
+        // 直接外されても階層が食い違わないようにする
+        let mut hooks = Hooks::default();
+        hooks.add(
+            TypeId::of::<Parent>(),
+            HookKind::Remove,
+            contains_component::<Parent>,
+            Box::new(hierarchy::on_remove_parent),
+        );
+        hooks.add(
+            TypeId::of::<Children>(),
+            HookKind::Remove,
+            contains_component::<Children>,
+            Box::new(hierarchy::on_remove_children),
+        );
         Self {
             entities: GenerationalVec::new(),
-            components: Components::new(),
+            components,
+            archetypes: Archetypes::new(),
+            resources: Resources::new(),
+            command_queue: Mutex::new(CommandQueue::default()),
+            removals: Removals::new(),
+            event_updaters: Vec::new(),
+            hooks,
+            observers: Observers::default(),
+            relations: Relations::default(),
+            reserved_entities: AtomicUsize::new(0),
+            // Tick::default() は「まだ実行されていない」を表すので 1 から始める
+            change_tick: AtomicU64::new(1),
         }
     }
 
@@ -54,121 +228,1596 @@ impl World {
     }
 
     pub fn new_entity(&mut self) -> GenerationalId {
-        self.entities.add(())
+        self.materialize_reserved_entities();
+        let entity = self.entities.add(());
+        self.archetypes.push(entity, Archetypes::EMPTY);
+        entity
     }
 
-    /// エンティティにコンポーネントを追加する
+    /// Reserve the ID of a new entity through a shared reference, e.g. from a system.
+    ///
+    /// The ID is valid immediately and never handed out again, but the entity is not alive until
+    /// it is spawned with no components by the next [`World::flush`] or [`World::new_entity`].
+    /// Commands recorded with the ID are applied after that, so they can reference entities
+    /// spawned by [`Commands::spawn`](crate::ecs::Commands::spawn) in the same system.
+    ///
+    /// Reserved entities always take new slots instead of the slots of despawned entities.
+    ///
+    /// ```
+    /// use xanadu::ecs::World;
+    ///
+    /// let mut world = World::builder().register_component::<u32>().build();
+    /// let entity = world.reserve_entity();
+    /// assert!(!world.is_alive(entity));
+    ///
+    /// world.flush();
+    /// assert!(world.is_alive(entity));
+    /// world.attach_component(entity, 1u32);
+    /// ```
+    pub fn reserve_entity(&self) -> GenerationalId {
+        let offset = self.reserved_entities.fetch_add(1, Ordering::Relaxed);
+        // 実体化されるまで entities は変更されないので、末尾から順に番号を振る
+        GenerationalId {
+            index: self.entities.index_len() + offset,
+            generation: 0,
+        }
+    }
+
+    /// 予約されたエンティティを空のアーキタイプに追加する
+    fn materialize_reserved_entities(&mut self) {
+        let reserved = std::mem::take(self.reserved_entities.get_mut());
+        for _ in 0..reserved {
+            let entity = self.entities.push(());
+            self.archetypes.push(entity, Archetypes::EMPTY);
+        }
+    }
+
+    /// Spawn a new entity with the components in the bundle.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if some of the components are not registered, or if a component type appears more
+    /// than once in the bundle. Use [`World::try_spawn`] to handle the error instead.
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> GenerationalId {
+        self.try_spawn(bundle)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Spawn a new entity with the components in the bundle, or return
+    /// [`Error::UnregisteredComponent`] or [`Error::DuplicateComponent`] without spawning it.
+    pub fn try_spawn<B: Bundle>(&mut self, bundle: B) -> Result<GenerationalId, Error> {
+        let types = self.check_bundle::<B>()?;
+        let tables = self.table_types(&types);
+        let archetype = self.archetypes.with_all(Archetypes::EMPTY, tables);
+        Ok(self.spawn_bundle(archetype, &types, bundle))
+    }
+
+    /// Spawn new entities with the components in the bundles.
     ///
+    /// This is faster than spawning them one by one, because the capacity of the entities and the
+    /// component storages is reserved up front for the number of bundles the iterator reports by
+    /// its size hint. Like [`World::spawn`], each entity is added directly to the archetype which
+    /// has all of its table components.
     ///
     /// ## Returns
     ///
-    /// 以前のコンポーネントがあればそれを返す。なければNoneを返す
-    pub fn attach_component<T: Component>(
+    /// Returns the spawned entities in the order of the bundles.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if some of the components are not registered, or if a component type appears more
+    /// than once in the bundle. Use [`World::try_spawn_batch`] to handle the error instead.
+    pub fn spawn_batch<I>(&mut self, bundles: I) -> Vec<GenerationalId>
+    where
+        I: IntoIterator,
+        I::Item: Bundle,
+    {
+        self.try_spawn_batch(bundles)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Spawn new entities with the components in the bundles like [`World::spawn_batch`], or
+    /// return [`Error::UnregisteredComponent`] or [`Error::DuplicateComponent`] without spawning
+    /// any of them.
+    pub fn try_spawn_batch<I>(&mut self, bundles: I) -> Result<Vec<GenerationalId>, Error>
+    where
+        I: IntoIterator,
+        I::Item: Bundle,
+    {
+        let types = self.check_bundle::<I::Item>()?;
+        self.materialize_reserved_entities();
+        let bundles = bundles.into_iter();
+        let additional = bundles.size_hint().0;
+
+        // 全てのテーブルのコンポーネントを持つアーキタイプに行を確保する
+        let tables = self.table_types(&types);
+        let archetype = self.archetypes.with_all(Archetypes::EMPTY, tables);
+        let index_len = self.entities.index_len_after(additional);
+        self.entities.reserve(additional);
+        self.archetypes.reserve(archetype, index_len, additional);
+        self.components
+            .reserve(types.iter().copied(), index_len, archetype, additional);
+
+        let mut entities = Vec::with_capacity(additional);
+        for bundle in bundles {
+            entities.push(self.spawn_bundle(archetype, &types, bundle));
+        }
+        Ok(entities)
+    }
+
+    /// Attach the components in the bundle to the entity, replacing the ones already attached.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if some of the components are not registered, if a component type appears more than
+    /// once in the bundle, or if the entity has already been despawned. Use
+    /// [`World::try_insert_bundle`] to handle the error instead.
+    pub fn insert_bundle<B: Bundle>(&mut self, entity: GenerationalId, bundle: B) {
+        self.try_insert_bundle(entity, bundle)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Attach the components in the bundle to the entity, or return an error without attaching
+    /// any of them.
+    ///
+    /// ## Returns
+    ///
+    /// Returns [`Error::UnregisteredComponent`] if some of the components are not registered,
+    /// [`Error::DuplicateComponent`] if a component type appears more than once in the bundle,
+    /// and [`Error::DeadEntity`] if the entity has already been despawned.
+    pub fn try_insert_bundle<B: Bundle>(
         &mut self,
         entity: GenerationalId,
-        component: T,
-    ) -> Option<T> {
-        if let Some(mut array) = self.components.borrow_mut::<T>() {
-            return array.borrow_mut().replace(entity.index, component);
+        bundle: B,
+    ) -> Result<(), Error> {
+        let types = self.check_bundle::<B>()?;
+        if !self.is_alive(entity) {
+            return Err(Error::DeadEntity(entity));
         }
-        None
+        self.write_bundle(entity, &types, bundle);
+        Ok(())
     }
 
-    pub fn execute<'world, T>(&'world mut self, system: impl System<'world, T>) {
-        system.execute(self);
+    /// Detach the components in the bundle from the entity.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the bundle, or None if some of the components were not attached. The other
+    /// components are detached anyway.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if some of the components are not registered, if a component type appears more than
+    /// once in the bundle, or if the entity has already been despawned. Use
+    /// [`World::try_remove_bundle`] to handle the error instead.
+    pub fn remove_bundle<B: Bundle>(&mut self, entity: GenerationalId) -> Option<B> {
+        self.try_remove_bundle(entity)
+            .unwrap_or_else(|err| panic!("{}", err))
     }
-}
 
-impl Default for World {
-    fn default() -> Self {
-        Self::new()
+    /// Detach the components in the bundle from the entity like [`World::remove_bundle`], or
+    /// return an error without detaching any of them.
+    ///
+    /// ## Returns
+    ///
+    /// Returns [`Error::UnregisteredComponent`] if some of the components are not registered,
+    /// [`Error::DuplicateComponent`] if a component type appears more than once in the bundle,
+    /// and [`Error::DeadEntity`] if the entity has already been despawned.
+    pub fn try_remove_bundle<B: Bundle>(
+        &mut self,
+        entity: GenerationalId,
+    ) -> Result<Option<B>, Error> {
+        let types = self.check_bundle::<B>()?;
+        if !self.is_alive(entity) {
+            return Err(Error::DeadEntity(entity));
+        }
+        Ok(self.take_bundle(entity, &types))
     }
-}
-
-pub struct Components {
-    map: HashMap<TypeId, RefCell<TypeErasedSparseVec>>,
-}
 
-impl Components {
-    fn new() -> Self {
-        Self {
-            map: HashMap::new(),
+    /// Check if every component in the bundle is registered and appears only once, and return
+    /// their types.
+    fn check_bundle<B: Bundle>(&self) -> Result<Vec<TypeId>, Error> {
+        let mut types = Vec::new();
+        B::component_types(&mut types);
+        for (i, &(type_id, name)) in types.iter().enumerate() {
+            if !self.components.contains_id(type_id) {
+                return Err(Error::UnregisteredComponent(name));
+            }
+            // 同じ列に二度追加すると行がずれる
+            if types[..i].iter().any(|&(other, _)| other == type_id) {
+                return Err(Error::DuplicateComponent(name));
+            }
         }
+        Ok(types.into_iter().map(|(type_id, _)| type_id).collect())
     }
 
-    fn register<T: Component>(&mut self) {
-        self.map.insert(
-            TypeId::of::<T>(),
-            RefCell::new(SparseVec::<T>::new().into()),
-        );
+    /// 型のうち、テーブルに格納されるものを返す
+    fn table_types(&mut self, types: &[TypeId]) -> Vec<TypeId> {
+        types
+            .iter()
+            .copied()
+            .filter(|&type_id| self.components.is_table_id(type_id))
+            .collect()
     }
 
-    pub(crate) fn get_exclusive_iter_mut<T: Component>(
+    /// バンドルのテーブルのコンポーネントを全て持つアーキタイプに、新しいエンティティを直接追加する。
+    /// 途中のアーキタイプや空のアーキタイプを経由しない
+    fn spawn_bundle<B: Bundle>(
         &mut self,
-    ) -> Option<std::slice::IterMut<'_, Option<T>>> {
-        let refcell = self.map.get_mut(&TypeId::of::<T>())?;
-        let optional_vec = refcell.get_mut().downcast_mut::<T>();
-        // SAFETY:
-        // self.map[TypeId::of<T>] には SparseVec<T> が登録されているので、ダウンキャストは必ず成功する
-        let vec = unsafe { optional_vec.unwrap_unchecked() };
-        Some(vec.data_iter_mut())
-    }
-
-    pub(crate) fn borrow_slice<T: Component>(&self) -> Option<Ref<'_, [Option<T>]>> {
-        let refcell = self.map.get(&TypeId::of::<T>())?;
-        let slice = Ref::map(refcell.borrow(), |vec| {
-            // SAFETY:
-            // self.map[TypeId::of<T>] には SparseVec<T> が登録されているので、ダウンキャストは必ず成功する
-            unsafe { vec.downcast::<T>().unwrap_unchecked() }.data_slice()
-        });
-        Some(slice)
+        archetype: usize,
+        types: &[TypeId],
+        bundle: B,
+    ) -> GenerationalId {
+        self.materialize_reserved_entities();
+        let entity = self.entities.add(());
+        let location = self.archetypes.push(entity, archetype);
+        let tick = self.change_tick();
+        bundle.write(&mut BundleWriter::new(
+            &mut self.components,
+            entity,
+            location,
+            tick,
+        ));
+        let mut hooked = false;
+        for &type_id in types {
+            hooked |= self.hooks.call(self, type_id, HookKind::Add, entity)
+                | self.hooks.call(self, type_id, HookKind::Insert, entity);
+        }
+        if hooked {
+            self.flush();
+        }
+        entity
     }
 
-    pub(crate) fn borrow_mut_slice<T: Component>(&self) -> Option<RefMut<'_, [Option<T>]>> {
-        let refcell = self.map.get(&TypeId::of::<T>())?;
-        let slice = RefMut::map(refcell.borrow_mut(), |vec| {
-            // SAFETY:
-            // self.map[TypeId::of<T>] には SparseVec<T> が登録されているので、ダウンキャストは必ず成功する
-            unsafe { vec.downcast_mut::<T>().unwrap_unchecked() }.data_mut_slice()
-        });
-        Some(slice)
+    /// バンドルのコンポーネントを生存しているエンティティに書き込む。
+    /// テーブルのコンポーネントが増える場合も、エンティティは一度だけ移動する
+    fn write_bundle<B: Bundle>(&mut self, entity: GenerationalId, types: &[TypeId], bundle: B) {
+        let Some(mut location) = self.location(entity) else {
+            return;
+        };
+        let attached: Vec<_> = types
+            .iter()
+            .map(|&type_id| self.components.contains_at(type_id, location))
+            .collect();
+        let mut hooked = false;
+        for (&type_id, _) in types
+            .iter()
+            .zip(&attached)
+            .filter(|(_, &attached)| attached)
+        {
+            hooked |= self.hooks.call(self, type_id, HookKind::Replace, entity);
+        }
+        let tables = self.table_types(types);
+        let archetype = self.archetypes.with_all(location.archetype, tables);
+        if archetype != location.archetype {
+            location = self.move_entity(location, archetype);
+        }
+        let tick = self.change_tick();
+        bundle.write(&mut BundleWriter::new(
+            &mut self.components,
+            entity,
+            location,
+            tick,
+        ));
+        for (&type_id, &attached) in types.iter().zip(&attached) {
+            if !attached {
+                hooked |= self.hooks.call(self, type_id, HookKind::Add, entity);
+            }
+            hooked |= self.hooks.call(self, type_id, HookKind::Insert, entity);
+        }
+        if hooked {
+            self.flush();
+        }
+    }
+
+    /// バンドルのコンポーネントを生存しているエンティティから取り除く。
+    /// テーブルのコンポーネントを全て行から取り除いてから、エンティティを一度だけ移動する
+    fn take_bundle<B: Bundle>(&mut self, entity: GenerationalId, types: &[TypeId]) -> Option<B> {
+        let location = self.location(entity)?;
+        let mut hooked = false;
+        for &type_id in types {
+            if self.components.contains_at(type_id, location) {
+                hooked |= self.hooks.call(self, type_id, HookKind::Replace, entity)
+                    | self.hooks.call(self, type_id, HookKind::Remove, entity);
+            }
+        }
+        let tick = self.change_tick();
+        let bundle = B::take(&mut BundleReader::new(
+            &mut self.components,
+            &mut self.removals,
+            entity,
+            location,
+            tick,
+        ));
+        let archetype = self.archetypes.without_all(location.archetype, types);
+        if archetype != location.archetype {
+            self.move_entity(location, archetype);
+        }
+        if hooked {
+            self.flush();
+        }
+        bundle
     }
 
-    pub(crate) fn borrow_mut<T: Component>(&self) -> Option<RefMut<'_, SparseVec<T>>> {
-        let refcell = self.map.get(&TypeId::of::<T>())?;
-        let vec = RefMut::map(refcell.borrow_mut(), |vec| {
-            // SAFETY:
-            // self.map[TypeId::of<T>] には SparseVec<T> が登録されているので、ダウンキャストは必ず成功する
-            unsafe { vec.downcast_mut::<T>().unwrap_unchecked() }
+    /// Despawn an entity and drop every component attached to it.
+    ///
+    /// The slot of the entity may be reused by a later [`World::new_entity`], but the reused ID
+    /// has a different generation, so the despawned ID never refers to the new entity.
+    ///
+    /// The entity is removed from the children of its parent, and its children become roots. Use
+    /// [`World::despawn_recursive`] to despawn the children as well. The pairs of relations with
+    /// the entity are removed, and the related entities are despawned as well if the
+    /// [`RelationPolicy`] says so.
+    ///
+    /// ## Returns
+    ///
+    /// Returns false if the entity has already been despawned.
+    pub fn despawn(&mut self, entity: GenerationalId) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        self.remove_parent(entity);
+        for child in self.children(entity) {
+            self.remove_parent(child);
+        }
+        // 階層の変更でテーブルの行が動くことがあるので、その後で位置を求める
+        let Some(location) = self.location(entity) else {
+            return false;
+        };
+        let hooked = self
+            .hooks
+            .call_attached(self, &[HookKind::Replace, HookKind::Remove], entity);
+        self.entities.remove(entity);
+        let tick = self.change_tick();
+        let types = self.archetypes.get(location.archetype).types();
+        for &type_id in types {
+            self.removals.record(type_id, entity, tick);
+        }
+        self.components.swap_remove_row(types, location);
+        self.archetypes.swap_remove(location);
+        let removals = &mut self.removals;
+        self.components.remove_sparse(entity.index, |type_id| {
+            removals.record(type_id, entity, tick)
         });
-        Some(vec)
+        if hooked {
+            self.flush();
+        }
+        for related in self.relations.remove_entity(entity) {
+            self.despawn(related);
+        }
+        true
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    /// Despawn an entity and all of its descendants.
+    ///
+    /// ## Returns
+    ///
+    /// Returns false if the entity has already been despawned.
+    pub fn despawn_recursive(&mut self, entity: GenerationalId) -> bool {
+        let descendants: Vec<_> = self.hierarchy().descendants(entity).collect();
+        if !self.despawn(entity) {
+            return false;
+        }
+        for descendant in descendants {
+            self.despawn(descendant);
+        }
+        true
+    }
 
-    #[test]
-    fn new() {
-        let world = World::new();
-        assert!(world.entities.is_empty());
-        assert!(world.components.map.is_empty());
+    /// Make `parent` the parent of `child`, removing `child` from the children of its previous
+    /// parent.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if either entity has already been despawned, or if `parent` is `child` itself or one
+    /// of its descendants. Use [`World::try_set_parent`] to handle the error instead.
+    pub fn set_parent(&mut self, child: GenerationalId, parent: GenerationalId) {
+        self.try_set_parent(child, parent)
+            .unwrap_or_else(|err| panic!("{}", err))
     }
 
-    #[test]
-    fn new_entity() {
-        let mut world = World::new();
-        let entity = world.new_entity();
-        assert_eq!(world.entities.len(), 1);
-        assert_eq!(world.entities.get(entity), Some(&()));
+    /// Make `parent` the parent of `child` like [`World::set_parent`], or return an error.
+    ///
+    /// ## Returns
+    ///
+    /// Returns [`Error::DeadEntity`] if either entity has already been despawned, and
+    /// [`Error::CyclicHierarchy`] if `parent` is `child` itself or one of its descendants.
+    pub fn try_set_parent(
+        &mut self,
+        child: GenerationalId,
+        parent: GenerationalId,
+    ) -> Result<(), Error> {
+        for entity in [child, parent] {
+            if !self.is_alive(entity) {
+                return Err(Error::DeadEntity(entity));
+            }
+        }
+        if parent == child
+            || self
+                .hierarchy()
+                .ancestors(parent)
+                .any(|ancestor| ancestor == child)
+        {
+            return Err(Error::CyclicHierarchy(child));
+        }
+        if self.parent(child) == Some(parent) {
+            return Ok(());
+        }
+        self.remove_parent(child);
+        self.attach_component(child, Parent(parent));
+        match self.get_component_mut::<Children>(parent) {
+            Some(mut children) => children.0.push(child),
+            None => {
+                self.attach_component(parent, Children(vec![child]));
+            }
+        }
+        Ok(())
     }
 
-    #[test]
-    fn attach_component_return_value() {
-        let mut world = World::builder().register_component::<i32>().build();
-        let entity = world.new_entity();
-        assert_eq!(world.attach_component(entity, 42), None);
-        assert_eq!(world.attach_component(entity, 43), Some(42));
+    /// Make the entity a root, removing it from the children of its parent.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the previous parent, or None if the entity had no parent or is not alive.
+    pub fn remove_parent(&mut self, child: GenerationalId) -> Option<GenerationalId> {
+        let parent = self.parent(child)?;
+        // 親の Children からはフックで取り除かれる
+        self.detach_component::<Parent>(child);
+        Some(parent)
+    }
+
+    /// 親の Children から子を取り除き、空になれば Children を外す
+    pub(crate) fn remove_child(&mut self, parent: GenerationalId, child: GenerationalId) {
+        if let Some(mut children) = self.get_component_mut::<Children>(parent) {
+            children.0.retain(|&entity| entity != child);
+            if children.is_empty() {
+                self.detach_component::<Children>(parent);
+            }
+        }
+    }
+
+    /// Returns the parent of the entity, or None if it has no parent or is not alive.
+    pub fn parent(&self, entity: GenerationalId) -> Option<GenerationalId> {
+        self.get_component::<Parent>(entity)
+            .map(|parent| parent.get())
+    }
+
+    /// Returns the children of the entity in the order they were added.
+    pub fn children(&self, entity: GenerationalId) -> Vec<GenerationalId> {
+        self.get_component::<Children>(entity)
+            .map_or_else(Vec::new, |children| children.to_vec())
+    }
+
+    /// Borrow the hierarchy to walk the ancestors or the descendants of entities.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if [`Parent`] or [`Children`] is mutably borrowed, e.g. by a running system. Use
+    /// [`World::try_hierarchy`] to handle the error instead.
+    pub fn hierarchy(&self) -> Hierarchy<'_> {
+        self.try_hierarchy().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Borrow the hierarchy like [`World::hierarchy`], or return [`Error::BorrowConflict`] if
+    /// [`Parent`] or [`Children`] is mutably borrowed.
+    pub fn try_hierarchy(&self) -> Result<Hierarchy<'_>, Error> {
+        Hierarchy::new(self)
+    }
+
+    /// Relate `source` to `target` with the relation type `R`.
+    ///
+    /// ## Returns
+    ///
+    /// Returns false if they are already related.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the relation type is not registered, or if either entity has already been
+    /// despawned. Use [`World::try_relate`] to handle the error instead.
+    pub fn relate<R: Relation>(&mut self, source: GenerationalId, target: GenerationalId) -> bool {
+        self.try_relate::<R>(source, target)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Relate `source` to `target` with the relation type `R`.
+    ///
+    /// ## Returns
+    ///
+    /// Returns `Ok(false)` if they are already related.
+    ///
+    /// Returns [`Error::UnregisteredRelation`] if the relation type is not registered, and
+    /// [`Error::DeadEntity`] if either entity has already been despawned.
+    pub fn try_relate<R: Relation>(
+        &mut self,
+        source: GenerationalId,
+        target: GenerationalId,
+    ) -> Result<bool, Error> {
+        let dead = [source, target]
+            .iter()
+            .copied()
+            .find(|&entity| !self.is_alive(entity));
+        let storage = self.relations.get_mut::<R>()?;
+        if let Some(entity) = dead {
+            return Err(Error::DeadEntity(entity));
+        }
+        Ok(storage.insert(source, target))
+    }
+
+    /// Remove the pair of `source` and `target` of the relation type `R`.
+    ///
+    /// ## Returns
+    ///
+    /// Returns false if they are not related.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the relation type is not registered.
+    pub fn unrelate<R: Relation>(
+        &mut self,
+        source: GenerationalId,
+        target: GenerationalId,
+    ) -> bool {
+        self.relations
+            .get_mut::<R>()
+            .unwrap_or_else(|err| panic!("{}", err))
+            .remove(source, target)
+    }
+
+    /// Returns the targets of the source entity in the order they were related.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the relation type is not registered.
+    pub fn targets<R: Relation>(&self, source: GenerationalId) -> &[GenerationalId] {
+        self.relations
+            .get::<R>()
+            .unwrap_or_else(|err| panic!("{}", err))
+            .targets(source)
+    }
+
+    /// Returns the sources of the target entity in the order they were related.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the relation type is not registered.
+    pub fn sources<R: Relation>(&self, target: GenerationalId) -> &[GenerationalId] {
+        self.relations
+            .get::<R>()
+            .unwrap_or_else(|err| panic!("{}", err))
+            .sources(target)
+    }
+
+    /// Check if an entity has been created and not despawned yet.
+    pub fn is_alive(&self, entity: GenerationalId) -> bool {
+        self.entities.get(entity).is_some()
+    }
+
+    /// Returns the location of the entity, or None if it is not alive.
+    fn location(&self, entity: GenerationalId) -> Option<EntityLocation> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.archetypes.location(entity.index)
+    }
+
+    /// Move the table components of the entity to another archetype.
+    ///
+    /// Only the components which both archetypes have are moved. The caller must add or remove the
+    /// other component.
+    fn move_entity(&mut self, location: EntityLocation, archetype: usize) -> EntityLocation {
+        let to = self.archetypes.get(archetype);
+        let types = self
+            .archetypes
+            .get(location.archetype)
+            .types()
+            .iter()
+            .filter(|&&type_id| to.contains_id(type_id));
+        self.components.move_row(types, location, archetype);
+        self.archetypes.move_entity(location, archetype)
+    }
+
+    /// エンティティにコンポーネントを追加する
+    ///
+    ///
+    /// ## Returns
+    ///
+    /// 以前のコンポーネントがあればそれを返す。なければNoneを返す
+    ///
+    /// ## Panics
+    ///
+    /// コンポーネントが登録されていない場合、またはエンティティが既にdespawnされている場合はパニックする。
+    /// パニックさせたくない場合は [`World::try_attach_component`] を使う
+    ///
+    /// 以前のバージョンではこれらの場合にNoneを返していた
+    pub fn attach_component<T: Component>(
+        &mut self,
+        entity: GenerationalId,
+        component: T,
+    ) -> Option<T> {
+        self.try_attach_component(entity, component)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// エンティティにコンポーネントを追加する
+    ///
+    /// ## Returns
+    ///
+    /// 以前のコンポーネントがあればそれを返す。なければ`Ok(None)`を返す
+    ///
+    /// コンポーネントが登録されていない場合は [`Error::UnregisteredComponent`] を、
+    /// エンティティが既にdespawnされている場合は [`Error::DeadEntity`] を返す
+    pub fn try_attach_component<T: Component>(
+        &mut self,
+        entity: GenerationalId,
+        component: T,
+    ) -> Result<Option<T>, Error> {
+        let location = self.location(entity).ok_or(Error::DeadEntity(entity))?;
+        let replacing = self
+            .components
+            .get_mut::<T>()?
+            .get_ticked(location)
+            .is_some();
+        let mut hooked = replacing && self.call_hooks::<T>(HookKind::Replace, entity);
+        let old = self.insert_component(entity, location, component)?;
+        if !replacing {
+            hooked |= self.call_hooks::<T>(HookKind::Add, entity);
+        }
+        hooked |= self.call_hooks::<T>(HookKind::Insert, entity);
+        if hooked {
+            self.flush();
+        }
+        Ok(old)
+    }
+
+    fn insert_component<T: Component>(
+        &mut self,
+        entity: GenerationalId,
+        location: EntityLocation,
+        component: T,
+    ) -> Result<Option<T>, Error> {
+        let tick = self.change_tick();
+        let storage = self.components.get_mut::<T>()?;
+        if let Some(old) = storage.get_ticked_mut(location) {
+            old.ticks.changed = tick;
+            return Ok(Some(std::mem::replace(&mut old.value, component)));
+        }
+        let component = Ticked::new(component, tick);
+        match storage {
+            Storage::SparseVec(vec) => vec
+                .insert(entity, component)
+                .map(|_| None)
+                .map_err(|_| Error::DeadEntity(entity)),
+            Storage::SparseSet(set) => {
+                set.insert(entity, component);
+                Ok(None)
+            }
+            Storage::Table(_) => {
+                let archetype = self.archetypes.with(location.archetype, TypeId::of::<T>());
+                let location = self.move_entity(location, archetype);
+                // move_entity が他のテーブルの行を動かした後で、同じ行に追加する
+                if let Storage::Table(table) = self.components.get_mut::<T>()? {
+                    table.push(location.archetype, component);
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// エンティティからコンポーネントを取り除く
+    ///
+    /// ## Returns
+    ///
+    /// 取り除いたコンポーネントを返す。コンポーネントが付いていなければNoneを返す
+    ///
+    /// ## Panics
+    ///
+    /// コンポーネントが登録されていない場合、またはエンティティが既にdespawnされている場合はパニックする。
+    /// パニックさせたくない場合は [`World::try_detach_component`] を使う
+    pub fn detach_component<T: Component>(&mut self, entity: GenerationalId) -> Option<T> {
+        self.try_detach_component(entity)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// エンティティからコンポーネントを取り除く
+    ///
+    /// ## Returns
+    ///
+    /// 取り除いたコンポーネントを返す。コンポーネントが付いていなければ`Ok(None)`を返す
+    ///
+    /// コンポーネントが登録されていない場合は [`Error::UnregisteredComponent`] を、
+    /// エンティティが既にdespawnされている場合は [`Error::DeadEntity`] を返す
+    pub fn try_detach_component<T: Component>(
+        &mut self,
+        entity: GenerationalId,
+    ) -> Result<Option<T>, Error> {
+        let location = self.location(entity).ok_or(Error::DeadEntity(entity))?;
+        if self
+            .components
+            .get_mut::<T>()?
+            .get_ticked(location)
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let hooked = self.call_hooks::<T>(HookKind::Replace, entity)
+            | self.call_hooks::<T>(HookKind::Remove, entity);
+        let component = match self.components.get_mut::<T>()? {
+            Storage::SparseVec(vec) => vec.remove(entity),
+            Storage::SparseSet(set) => set.remove(entity.index),
+            Storage::Table(table) => {
+                let component = table.swap_remove(location);
+                let archetype = self
+                    .archetypes
+                    .without(location.archetype, TypeId::of::<T>());
+                self.move_entity(location, archetype);
+                Some(component)
+            }
+        };
+        if component.is_some() {
+            let tick = self.change_tick();
+            self.removals.record(TypeId::of::<T>(), entity, tick);
+        }
+        if hooked {
+            self.flush();
+        }
+        Ok(component.map(|ticked| ticked.value))
+    }
+
+    /// Call the hooks of the kind for the component type.
+    ///
+    /// ## Returns
+    ///
+    /// Returns false if no hooks were called.
+    fn call_hooks<T: Component>(&self, kind: HookKind, entity: Entity) -> bool {
+        self.hooks.call(self, TypeId::of::<T>(), kind, entity)
+    }
+
+    /// Get a reference to the component of the entity.
+    ///
+    /// Returns None if the entity is not alive or the component is not attached to it.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the component is not registered, or if the components of the type are mutably
+    /// borrowed, e.g. by a running system. Use [`World::try_get_component`] to handle the error
+    /// instead.
+    pub fn get_component<T: Component>(&self, entity: GenerationalId) -> Option<AtomicRef<'_, T>> {
+        self.try_get_component(entity)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Get a reference to the component of the entity like [`World::get_component`], or return
+    /// [`Error::UnregisteredComponent`] if the component is not registered, or
+    /// [`Error::BorrowConflict`] if the components of the type are mutably borrowed.
+    pub fn try_get_component<T: Component>(
+        &self,
+        entity: GenerationalId,
+    ) -> Result<Option<AtomicRef<'_, T>>, Error> {
+        let storage = self.components.borrow::<T>()?;
+        let Some(location) = self.location(entity) else {
+            return Ok(None);
+        };
+        Ok(AtomicRef::filter_map(storage, |storage| storage.get(location)).ok())
+    }
+
+    /// Get a mutable reference to the component of the entity, which marks the component changed
+    /// when it is dereferenced mutably.
+    ///
+    /// Returns None if the entity is not alive or the component is not attached to it.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the component is not registered. Use [`World::try_get_component_mut`] to handle
+    /// the error instead.
+    pub fn get_component_mut<T: Component>(
+        &mut self,
+        entity: GenerationalId,
+    ) -> Option<Mut<'_, T>> {
+        self.try_get_component_mut(entity)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Get a mutable reference to the component of the entity like [`World::get_component_mut`],
+    /// or return [`Error::UnregisteredComponent`] if the component is not registered.
+    pub fn try_get_component_mut<T: Component>(
+        &mut self,
+        entity: GenerationalId,
+    ) -> Result<Option<Mut<'_, T>>, Error> {
+        let location = self.location(entity);
+        let tick = self.change_tick();
+        let storage = self.components.get_mut::<T>()?;
+        let Some(location) = location else {
+            return Ok(None);
+        };
+        Ok(storage
+            .get_ticked_mut(location)
+            .map(|ticked| ticked.as_mut(tick)))
+    }
+
+    /// Check if the component is attached to the entity.
+    ///
+    /// Returns false if the entity is not alive.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the component is not registered, or if the components of the type are mutably
+    /// borrowed. Use [`World::try_has_component`] to handle the error instead.
+    pub fn has_component<T: Component>(&self, entity: GenerationalId) -> bool {
+        self.try_has_component::<T>(entity)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Check if the component is attached to the entity like [`World::has_component`], or return
+    /// the error of [`World::try_get_component`].
+    pub fn try_has_component<T: Component>(&self, entity: GenerationalId) -> Result<bool, Error> {
+        Ok(self.try_get_component::<T>(entity)?.is_some())
+    }
+
+    /// Insert a resource into the world.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the previous value of the resource, or None if it did not exist.
+    pub fn insert_resource<T: Resource>(&mut self, resource: T) -> Option<T> {
+        self.resources.insert(resource)
+    }
+
+    /// Remove a resource from the world.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the removed value, or None if the resource did not exist.
+    pub fn remove_resource<T: Resource>(&mut self) -> Option<T> {
+        self.resources.remove()
+    }
+
+    /// Get a reference to the resource, or None if it does not exist.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the resource is mutably borrowed, e.g. by a running system. Use
+    /// [`World::try_get_resource`] to handle the error instead.
+    pub fn get_resource<T: Resource>(&self) -> Option<AtomicRef<'_, T>> {
+        self.try_get_resource()
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Get a reference to the resource like [`World::get_resource`], or return
+    /// [`Error::ResourceBorrowConflict`] if the resource is mutably borrowed.
+    pub fn try_get_resource<T: Resource>(&self) -> Result<Option<AtomicRef<'_, T>>, Error> {
+        if !self.has_resource::<T>() {
+            return Ok(None);
+        }
+        self.resources.borrow::<T>().map(Some)
+    }
+
+    /// Get a mutable reference to the resource, or None if it does not exist.
+    pub fn get_resource_mut<T: Resource>(&mut self) -> Option<&mut T> {
+        self.resources.get_mut()
+    }
+
+    /// Check if the resource exists in the world.
+    pub fn has_resource<T: Resource>(&self) -> bool {
+        self.resources.contains::<T>()
+    }
+
+    /// Returns the current tick of the world.
+    ///
+    /// Components attached or mutated outside systems are marked with this tick.
+    pub fn change_tick(&self) -> Tick {
+        Tick(self.change_tick.load(Ordering::Acquire))
+    }
+
+    /// Advance the tick of the world, and return the tick before it as the tick of a system run.
+    pub(crate) fn increment_change_tick(&self) -> Tick {
+        Tick(self.change_tick.fetch_add(1, Ordering::AcqRel))
+    }
+
+    /// Drop the removals which [`RemovedComponents`](crate::ecs::RemovedComponents) yields, except
+    /// the ones recorded since the previous call.
+    ///
+    /// This is called at the end of each run of a [`Schedule`](crate::ecs::Schedule). Call this
+    /// periodically if systems are executed without a schedule.
+    pub fn clear_trackers(&mut self) {
+        let tick = self.change_tick();
+        self.removals.clear(tick);
+    }
+
+    /// Send an event, which is read by the [`EventReader`](crate::ecs::EventReader)s of the type.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the event type has not been added to the world. Use [`World::try_send_event`] to
+    /// handle the error instead.
+    pub fn send_event<E: Event>(&mut self, event: E) {
+        self.try_send_event(event)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Send an event, or return an error if the event type has not been added to the world.
+    pub fn try_send_event<E: Event>(&mut self, event: E) -> Result<(), Error> {
+        let tick = self.change_tick();
+        self.resources.borrow_mut::<Events<E>>()?.send(event, tick);
+        Ok(())
+    }
+
+    /// Add an observer, which is executed by [`World::trigger`] every time the event type is
+    /// triggered for any entity.
+    ///
+    /// Observers are systems which take a [`Trigger`](crate::ecs::Trigger) parameter in addition
+    /// to the other parameters systems can take. With the `parallel` feature, they must be `Send`
+    /// and `Sync`.
+    pub fn observe<E: Event, T>(&mut self, observer: impl System<T> + MaybeSendSync + 'static) {
+        self.observe_entities::<E, T>(Vec::new(), observer);
+    }
+
+    /// Add an observer, which is executed by [`World::trigger`] every time the event type is
+    /// triggered for the entity, or propagates to it.
+    pub fn observe_entity<E: Event, T>(
+        &mut self,
+        entity: Entity,
+        observer: impl System<T> + MaybeSendSync + 'static,
+    ) {
+        self.observe_entities::<E, T>(vec![entity], observer);
+    }
+
+    fn observe_entities<E: Event, T>(
+        &mut self,
+        entities: Vec<Entity>,
+        mut observer: impl System<T> + MaybeSendSync + 'static,
+    ) {
+        self.observers.add::<E>(
+            entities,
+            Box::new(move |world, last_run| observer.try_run_since(world, last_run)),
+        );
+    }
+
+    /// Trigger an event for the entity, executing its observers immediately.
+    ///
+    /// The observers added by [`World::observe_entity`] for the entity are executed, as well as
+    /// the ones added by [`World::observe`]. The commands recorded by the observers are applied
+    /// after all of them have finished.
+    ///
+    /// ## Returns
+    ///
+    /// Returns the event, which may have been modified by the observers.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the parameters of an observer cannot be taken from the world. Use
+    /// [`World::try_trigger`] to handle the error instead.
+    pub fn trigger<E: Event>(&mut self, event: E, entity: Entity) -> E {
+        self.try_trigger(event, entity)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Trigger an event for the entity like [`World::trigger`], or return an error if the
+    /// parameters of an observer cannot be taken from the world.
+    ///
+    /// If an observer fails, the remaining observers are not executed.
+    pub fn try_trigger<E: Event>(&mut self, event: E, entity: Entity) -> Result<E, Error> {
+        Observers::trigger(self, event, entity, None)
+    }
+
+    /// Trigger an event for the entity like [`World::trigger`], and then propagate it to the
+    /// entity which the component `T` of the entity links to, until an entity has no `T` or an
+    /// observer stops the propagation by [`Trigger::propagate`](crate::ecs::Trigger::propagate).
+    ///
+    /// The observers added by [`World::observe`] are executed only once, for the first entity.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the parameters of an observer cannot be taken from the world. Use
+    /// [`World::try_trigger_propagating`] to handle the error instead.
+    pub fn trigger_propagating<T: Traversal, E: Event>(&mut self, event: E, entity: Entity) -> E {
+        self.try_trigger_propagating::<T, E>(event, entity)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Trigger an event and propagate it like [`World::trigger_propagating`], or return an error
+    /// if the parameters of an observer cannot be taken from the world.
+    pub fn try_trigger_propagating<T: Traversal, E: Event>(
+        &mut self,
+        event: E,
+        entity: Entity,
+    ) -> Result<E, Error> {
+        Observers::trigger(self, event, entity, Some(traverse::<T>))
+    }
+
+    /// Drop the events sent before the previous call, for every event type added to the world.
+    ///
+    /// This is called at the end of each run of a [`Schedule`](crate::ecs::Schedule). Call this
+    /// periodically if systems are executed without a schedule.
+    pub fn update_events(&mut self) {
+        for update in self.event_updaters.clone() {
+            update(self);
+        }
+    }
+
+    /// Apply the changes recorded by [`Commands`](crate::ecs::Commands) which have not been
+    /// applied yet.
+    ///
+    /// Entities reserved by [`World::reserve_entity`] are spawned before the commands are applied.
+    ///
+    /// This is called automatically after each system executed by [`World::execute`].
+    pub fn flush(&mut self) {
+        self.materialize_reserved_entities();
+        // コマンドが更にコマンドを積むこともあるので、空になるまで繰り返す
+        loop {
+            let queue = self
+                .command_queue
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner);
+            if queue.is_empty() {
+                break;
+            }
+            std::mem::take(queue).apply(self);
+        }
+    }
+
+    /// Execute a system on the world, and apply the commands recorded by it.
+    ///
+    /// The system is executed as if it has never run, so [`Added`](crate::ecs::Added) and
+    /// [`Changed`](crate::ecs::Changed) filters match every component, and
+    /// [`EventReader`](crate::ecs::EventReader)s read every event kept in the queue. Use
+    /// [`Schedule`](crate::ecs::Schedule) to detect the changes since the previous run.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the parameters of the system cannot be taken from the world. Use
+    /// [`World::try_execute`] to handle the error instead.
+    pub fn execute<T>(&mut self, mut system: impl System<T>) {
+        system.execute(self);
+    }
+
+    /// Execute a system on the world and apply the commands recorded by it, or return an error if
+    /// the parameters of the system cannot be taken from the world.
+    pub fn try_execute<T>(&mut self, mut system: impl System<T>) -> Result<(), Error> {
+        system.try_execute(self)
+    }
+}
+
+fn contains_component<T: Component>(world: &World, entity: Entity) -> bool {
+    let Some(location) = world.location(entity) else {
+        return false;
+    };
+    world
+        .components
+        .borrow::<T>()
+        .is_ok_and(|storage| storage.get(location).is_some())
+}
+
+fn traverse<T: Traversal>(world: &World, entity: Entity) -> Option<Entity> {
+    let location = world.location(entity)?;
+    let storage = world.components.borrow::<T>().ok()?;
+    storage.get(location)?.next()
+}
+
+fn update_events<E: Event>(world: &mut World) {
+    if let Some(events) = world.get_resource_mut::<Events<E>>() {
+        events.update();
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ecs::{Query, Res, ResMut, With};
+
+    fn slot<T: Component + Copy>(world: &World, index: usize) -> Option<T> {
+        match &*world.components.borrow::<T>().unwrap() {
+            Storage::SparseVec(vec) => vec.data_slice()[index].as_ref().map(|ticked| ticked.value),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn new() {
+        let world = World::new();
+        assert!(world.entities.is_empty());
+        assert!(world.components.borrow::<i32>().is_err());
+    }
+
+    #[test]
+    fn new_entity() {
+        let mut world = World::new();
+        let entity = world.new_entity();
+        assert_eq!(world.entities.len(), 1);
+        assert_eq!(world.entities.get(entity), Some(&()));
+    }
+
+    #[test]
+    fn reserve_entity() {
+        let mut world = World::new();
+        let despawned = world.new_entity();
+        world.despawn(despawned);
+        let reserved: Vec<_> = (0..40).map(|_| world.reserve_entity()).collect();
+        assert!(reserved.iter().all(|&entity| !world.is_alive(entity)));
+
+        // 予約されたエンティティは、新しいエンティティより先に実体化される
+        let entity = world.new_entity();
+        assert_eq!(entity.index, despawned.index);
+        assert!(reserved.iter().all(|&entity| world.is_alive(entity)));
+        let next = world.reserve_entity();
+        world.flush();
+        assert!(world.is_alive(next));
+        assert_eq!(world.entities.len(), 42);
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    #[test]
+    fn non_thread_safe_values() {
+        use std::{cell::Cell, rc::Rc};
+
+        let shared = Rc::new(Cell::new(0));
+        let mut world = World::builder()
+            .register_component::<Rc<Cell<i32>>>()
+            .build();
+        world.insert_resource(Cell::new(1));
+        let cloned = Rc::clone(&shared);
+        world.execute(move |mut commands: Commands<'_>| {
+            commands.spawn().attach(Rc::clone(&cloned));
+        });
+        world.execute(
+            |query: Query<'_, &Rc<Cell<i32>>>, counter: Res<'_, Cell<i32>>| {
+                for value in query.iter() {
+                    value.set(counter.get());
+                }
+            },
+        );
+        assert_eq!(shared.get(), 1);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn reserve_entity_concurrently() {
+        let mut world = World::new();
+        let mut reserved: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| (0..10).map(|_| world.reserve_entity()).collect::<Vec<_>>())
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        reserved.sort_by_key(|entity| entity.index);
+        reserved.dedup();
+        assert_eq!(reserved.len(), 40);
+        world.flush();
+        assert!(reserved.iter().all(|&entity| world.is_alive(entity)));
+    }
+
+    #[test]
+    fn attach_component_return_value() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        assert_eq!(world.attach_component(entity, 42), None);
+        assert_eq!(world.attach_component(entity, 43), Some(42));
+    }
+
+    #[test]
+    fn despawn() {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<f32>()
+            .build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 42);
+        world.attach_component(entity, 4.2f32);
+        assert!(world.despawn(entity));
+        assert!(!world.is_alive(entity));
+        assert!(!world.despawn(entity));
+        assert_eq!(slot::<i32>(&world, 0), None);
+        assert_eq!(slot::<f32>(&world, 0), None);
+    }
+
+    #[test]
+    fn despawn_and_reuse_slot() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let old = world.new_entity();
+        world.attach_component(old, 42);
+        world.despawn(old);
+
+        let new = world.new_entity();
+        assert_eq!(new.index, old.index);
+        assert_eq!(slot::<i32>(&world, 0), None);
+
+        assert_eq!(
+            world.try_attach_component(old, 43),
+            Err(Error::DeadEntity(old))
+        );
+        assert_eq!(slot::<i32>(&world, 0), None);
+        assert_eq!(world.attach_component(new, 44), None);
+        assert_eq!(slot::<i32>(&world, 0), Some(44));
+    }
+
+    #[test]
+    fn stale_entity_cannot_overwrite() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let old = world.new_entity();
+        world.despawn(old);
+        let new = world.new_entity();
+        world.attach_component(new, 42);
+
+        assert_eq!(
+            world.try_attach_component(old, 43),
+            Err(Error::DeadEntity(old))
+        );
+        assert_eq!(world.get_component::<i32>(new).as_deref(), Some(&42));
+        assert_eq!(world.get_component::<i32>(old).as_deref(), None);
+    }
+
+    #[test]
+    fn try_attach_unregistered_component() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        assert_eq!(
+            world.try_attach_component(entity, 4.2f32),
+            Err(Error::UnregisteredComponent("f32"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "component `f32` is not registered")]
+    fn attach_unregistered_component() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 4.2f32);
+    }
+
+    #[test]
+    fn try_execute() {
+        let mut world = World::builder().register_component::<i32>().build();
+        assert_eq!(
+            world.try_execute(|_: Query<'_, &f32>| {}),
+            Err(Error::UnregisteredComponent("f32"))
+        );
+        assert_eq!(
+            world.try_execute(|_: Query<'_, (&mut i32, &mut i32)>| {}),
+            Err(Error::BorrowConflict("i32"))
+        );
+        assert_eq!(world.try_execute(|_: Query<'_, (&i32, &i32)>| {}), Ok(()));
+    }
+
+    #[test]
+    fn multi_parameter_system() {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<f32>()
+            .build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 1);
+        world.attach_component(entity, 2.0f32);
+        world.execute(|mut ints: Query<'_, &mut i32>, floats: Query<'_, &f32>| {
+            for (mut i, f) in ints.iter_mut().zip(floats.iter()) {
+                *i += *f as i32;
+            }
+        });
+        assert_eq!(world.get_component::<i32>(entity).as_deref(), Some(&3));
+        world.execute(|| {});
+    }
+
+    #[test]
+    fn sixteen_parameter_system() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 1);
+        let mut count = 0;
+        world.execute(
+            |q0: Query<'_, &i32>,
+             q1: Query<'_, &i32>,
+             q2: Query<'_, &i32>,
+             q3: Query<'_, &i32>,
+             q4: Query<'_, &i32>,
+             q5: Query<'_, &i32>,
+             q6: Query<'_, &i32>,
+             q7: Query<'_, &i32>,
+             q8: Query<'_, &i32>,
+             q9: Query<'_, &i32>,
+             q10: Query<'_, &i32>,
+             q11: Query<'_, &i32>,
+             q12: Query<'_, &i32>,
+             q13: Query<'_, &i32>,
+             q14: Query<'_, &i32>,
+             q15: Query<'_, &i32>| {
+                count += q0.iter().count()
+                    + q1.iter().count()
+                    + q2.iter().count()
+                    + q3.iter().count()
+                    + q4.iter().count()
+                    + q5.iter().count()
+                    + q6.iter().count()
+                    + q7.iter().count()
+                    + q8.iter().count()
+                    + q9.iter().count()
+                    + q10.iter().count()
+                    + q11.iter().count()
+                    + q12.iter().count()
+                    + q13.iter().count()
+                    + q14.iter().count()
+                    + q15.iter().count();
+            },
+        );
+        assert_eq!(count, 16);
+    }
+
+    #[test]
+    fn conflicting_access() {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<f32>()
+            .build();
+        assert_eq!(
+            world.try_execute(|_: Query<'_, &mut i32>, _: Query<'_, &i32>| {}),
+            Err(Error::ConflictingAccess("i32"))
+        );
+        assert_eq!(
+            world.try_execute(|_: Query<'_, &f32, With<i32>>, _: Query<'_, &mut i32>| {}),
+            Err(Error::ConflictingAccess("i32"))
+        );
+        assert_eq!(
+            world.try_execute(|_: Query<'_, &mut i32>, _: Query<'_, &mut f32>| {}),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn borrow_conflict() {
+        let world = World::builder().register_component::<i32>().build();
+        let _borrow = world.components.borrow::<i32>().unwrap();
+        assert!(world.components.borrow::<i32>().is_ok());
+        assert_eq!(
+            world.components.borrow_mut::<i32>().err(),
+            Some(Error::BorrowConflict("i32"))
+        );
+    }
+
+    #[test]
+    fn detach_component() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 42);
+        assert_eq!(world.detach_component::<i32>(entity), Some(42));
+        assert_eq!(world.detach_component::<i32>(entity), None);
+        assert!(!world.has_component::<i32>(entity));
+    }
+
+    #[test]
+    fn try_detach_component() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        assert_eq!(
+            world.try_detach_component::<f32>(entity),
+            Err(Error::UnregisteredComponent("f32"))
+        );
+        world.despawn(entity);
+        assert_eq!(
+            world.try_detach_component::<i32>(entity),
+            Err(Error::DeadEntity(entity))
+        );
+    }
+
+    #[test]
+    fn get_component() {
+        let mut world = World::builder()
+            .register_component::<i32>()
+            .register_component::<f32>()
+            .build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 42);
+        assert_eq!(world.get_component::<i32>(entity).as_deref(), Some(&42));
+        assert!(world.get_component::<f32>(entity).is_none());
+        assert!(world.has_component::<i32>(entity));
+        assert!(!world.has_component::<f32>(entity));
+    }
+
+    #[test]
+    fn get_component_mut() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 42);
+        *world.get_component_mut::<i32>(entity).unwrap() += 1;
+        assert_eq!(world.get_component::<i32>(entity).as_deref(), Some(&43));
+    }
+
+    #[test]
+    fn try_get_component_mut() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        assert!(matches!(
+            world.try_get_component_mut::<f32>(entity),
+            Err(Error::UnregisteredComponent("f32"))
+        ));
+        assert!(matches!(
+            world.try_get_component_mut::<i32>(entity),
+            Ok(None)
+        ));
+        world.attach_component(entity, 42);
+        *world.try_get_component_mut::<i32>(entity).unwrap().unwrap() += 1;
+        assert_eq!(world.get_component::<i32>(entity).as_deref(), Some(&43));
+    }
+
+    #[test]
+    fn get_component_of_stale_entity() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let old = world.new_entity();
+        world.attach_component(old, 42);
+        world.despawn(old);
+        let new = world.new_entity();
+        world.attach_component(new, 43);
+        assert!(world.get_component::<i32>(old).is_none());
+        assert!(world.get_component_mut::<i32>(old).is_none());
+        assert!(!world.has_component::<i32>(old));
+    }
+
+    #[test]
+    #[should_panic(expected = "component `f32` is not registered")]
+    fn get_unregistered_component() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        world.get_component::<f32>(entity);
+    }
+
+    #[test]
+    fn try_get_component() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 42);
+        assert_eq!(
+            world.try_get_component::<f32>(entity).err(),
+            Some(Error::UnregisteredComponent("f32"))
+        );
+        let storage = world.components.borrow_mut::<i32>().unwrap();
+        assert_eq!(
+            world.try_get_component::<i32>(entity).err(),
+            Some(Error::BorrowConflict("i32"))
+        );
+        assert_eq!(
+            world.try_has_component::<i32>(entity),
+            Err(Error::BorrowConflict("i32"))
+        );
+        drop(storage);
+        assert_eq!(world.try_has_component::<i32>(entity), Ok(true));
+    }
+
+    #[test]
+    fn resources() {
+        let mut world = World::builder().build();
+        assert_eq!(world.insert_resource(1i32), None);
+        assert_eq!(world.insert_resource(2i32), Some(1));
+        assert_eq!(world.get_resource::<i32>().as_deref(), Some(&2));
+        *world.get_resource_mut::<i32>().unwrap() += 1;
+        assert!(world.has_resource::<i32>());
+        assert_eq!(world.remove_resource::<i32>(), Some(3));
+        assert!(world.get_resource::<i32>().is_none());
+        assert!(!world.has_resource::<i32>());
+    }
+
+    #[test]
+    fn try_get_resource() {
+        let mut world = World::builder().build();
+        assert!(matches!(world.try_get_resource::<i32>(), Ok(None)));
+        world.insert_resource(1i32);
+        let borrow = world.resources.borrow_mut::<i32>().unwrap();
+        assert_eq!(
+            world.try_get_resource::<i32>().err(),
+            Some(Error::ResourceBorrowConflict("i32"))
+        );
+        drop(borrow);
+        assert_eq!(
+            world.try_get_resource::<i32>().unwrap().as_deref(),
+            Some(&1)
+        );
+    }
+
+    #[test]
+    fn resource_parameters() {
+        let mut world = World::builder().register_component::<i32>().build();
+        let entity = world.new_entity();
+        world.attach_component(entity, 1);
+        world.insert_resource(10i32);
+        world.insert_resource(0usize);
+        world.execute(
+            |mut query: Query<'_, &mut i32>, delta: Res<'_, i32>, mut count: ResMut<'_, usize>| {
+                for mut value in query.iter_mut() {
+                    *value += *delta;
+                    *count += 1;
+                }
+            },
+        );
+        assert_eq!(world.get_component::<i32>(entity).as_deref(), Some(&11));
+        assert_eq!(world.get_resource::<usize>().as_deref(), Some(&1));
+    }
+
+    #[test]
+    fn optional_resource_parameter() {
+        let mut world = World::builder().build();
+        let mut seen = None;
+        world.execute(|value: Option<Res<'_, i32>>| seen = Some(value.is_some()));
+        assert_eq!(seen, Some(false));
+        assert_eq!(
+            world.try_execute(|_: Res<'_, i32>| {}),
+            Err(Error::MissingResource("i32"))
+        );
+    }
+
+    #[test]
+    fn conflicting_resource_access() {
+        let mut world = World::builder().register_component::<i32>().build();
+        world.insert_resource(1i32);
+        assert_eq!(
+            world.try_execute(|_: Res<'_, i32>, _: ResMut<'_, i32>| {}),
+            Err(Error::ConflictingResourceAccess("i32"))
+        );
+        assert_eq!(
+            world.try_execute(|_: Query<'_, &mut i32>, _: ResMut<'_, i32>| {}),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn table_storage() {
+        let mut world = World::builder()
+            .register_component_with_storage::<i32>(StorageKind::Table)
+            .register_component_with_storage::<f32>(StorageKind::Table)
+            .register_component::<u32>()
+            .build();
+        let a = world.new_entity();
+        let b = world.new_entity();
+        world.attach_component(a, 1);
+        world.attach_component(b, 2);
+        world.attach_component(a, 1.5f32);
+        world.attach_component(b, 7u32);
+        assert_eq!(world.attach_component(a, 10), Some(1));
+        assert_eq!(world.get_component::<i32>(a).as_deref(), Some(&10));
+        assert_eq!(world.get_component::<f32>(a).as_deref(), Some(&1.5));
+        assert_eq!(world.get_component::<f32>(b).as_deref(), None);
+
+        assert_eq!(world.detach_component::<i32>(a), Some(10));
+        assert_eq!(world.detach_component::<i32>(a), None);
+        assert_eq!(world.get_component::<f32>(a).as_deref(), Some(&1.5));
+        assert_eq!(world.get_component::<i32>(b).as_deref(), Some(&2));
+
+        *world.get_component_mut::<i32>(b).unwrap() += 1;
+        assert!(world.despawn(a));
+        assert_eq!(world.get_component::<f32>(a).as_deref(), None);
+        assert_eq!(world.get_component::<i32>(b).as_deref(), Some(&3));
+        assert_eq!(world.get_component::<u32>(b).as_deref(), Some(&7));
+
+        let c = world.new_entity();
+        assert_eq!(c.index, a.index);
+        assert_eq!(world.get_component::<i32>(c).as_deref(), None);
+        assert_eq!(world.get_component::<f32>(c).as_deref(), None);
+    }
+
+    #[test]
+    fn sparse_set_storage() {
+        let mut world = World::builder()
+            .register_component_with_storage::<i32>(StorageKind::SparseSet)
+            .build();
+        let a = world.new_entity();
+        let b = world.new_entity();
+        world.attach_component(a, 1);
+        world.attach_component(b, 2);
+        assert_eq!(world.attach_component(a, 10), Some(1));
+        assert_eq!(world.detach_component::<i32>(a), Some(10));
+        assert_eq!(world.detach_component::<i32>(a), None);
+        *world.get_component_mut::<i32>(b).unwrap() += 1;
+        assert_eq!(world.get_component::<i32>(b).as_deref(), Some(&3));
+
+        assert!(world.despawn(b));
+        let c = world.new_entity();
+        assert_eq!(c.index, b.index);
+        assert_eq!(world.get_component::<i32>(c).as_deref(), None);
+        assert_eq!(world.try_attach_component(b, 4), Err(Error::DeadEntity(b)));
     }
 }
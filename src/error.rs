@@ -0,0 +1,50 @@
+use crate::collections::GenerationalId;
+
+/// Errors returned by the fallible `try_*` APIs of Xanadu
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    /// The component type has not been registered with
+    /// [`WorldBuilder::register_component`](crate::ecs::WorldBuilder::register_component).
+    #[error("component `{0}` is not registered")]
+    UnregisteredComponent(&'static str),
+    /// The component type appears more than once in a [`Bundle`](crate::ecs::Bundle).
+    #[error("component `{0}` appears more than once in the bundle")]
+    DuplicateComponent(&'static str),
+    /// The entity has already been despawned, or has never been created in the world.
+    #[error("entity {0:?} is not alive")]
+    DeadEntity(GenerationalId),
+    /// The component storage is already borrowed in a way that conflicts with the requested access.
+    #[error("component `{0}` is already borrowed")]
+    BorrowConflict(&'static str),
+    /// Two parameters of a system access the same component and at least one of them writes it.
+    #[error(
+        "component `{0}` is accessed by multiple parameters and at least one of them writes it"
+    )]
+    ConflictingAccess(&'static str),
+    /// The resource has not been inserted with
+    /// [`World::insert_resource`](crate::ecs::World::insert_resource), or has been removed.
+    #[error("resource `{0}` does not exist")]
+    MissingResource(&'static str),
+    /// The resource is already borrowed in a way that conflicts with the requested access.
+    #[error("resource `{0}` is already borrowed")]
+    ResourceBorrowConflict(&'static str),
+    /// Two parameters of a system access the same resource and at least one of them writes it.
+    #[error(
+        "resource `{0}` is accessed by multiple parameters and at least one of them writes it"
+    )]
+    ConflictingResourceAccess(&'static str),
+    /// The relation type has not been registered with
+    /// [`WorldBuilder::register_relation`](crate::ecs::WorldBuilder::register_relation).
+    #[error("relation `{0}` is not registered")]
+    UnregisteredRelation(&'static str),
+    /// The entity cannot be a child of itself or of its descendant.
+    #[error("entity {0:?} cannot be a child of itself or of its descendant")]
+    CyclicHierarchy(GenerationalId),
+    /// An ordering constraint of a [`Schedule`](crate::ecs::Schedule) refers to a label which no
+    /// system has.
+    #[error("no system has the label `{0}`")]
+    UnknownLabel(&'static str),
+    /// The ordering constraints of a [`Schedule`](crate::ecs::Schedule) form a cycle.
+    #[error("systems {0:?} cannot be ordered because of cyclic ordering constraints")]
+    CyclicDependency(Vec<&'static str>),
+}
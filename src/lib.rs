@@ -9,10 +9,7 @@
 //! ## Example
 //!
 //! ```rust
-//! use xanadu::ecs::{
-//!     PairComponentsRefIter, PairComponentsRefIterMut, SingleComponentExclusiveIter,
-//!     SingleComponentExclusiveIterMut, World,
-//! };
+//! use xanadu::ecs::{Query, World};
 //!
 //! #[derive(Debug)]
 //! pub struct Position {
@@ -77,14 +74,14 @@
 //!     world.execute(print2_system);
 //! }
 //!
-//! fn print_system(iter: SingleComponentExclusiveIter<'_, Position>) {
-//!     for pos in iter {
+//! fn print_system(query: Query<'_, &Position>) {
+//!     for pos in query.iter() {
 //!         println!("Pos: [{}, {}, {}]", pos.x, pos.y, pos.z);
 //!     }
 //! }
 //!
-//! fn shuffle_system(iter: SingleComponentExclusiveIterMut<'_, Position>) {
-//!     for pos in iter {
+//! fn shuffle_system(mut query: Query<'_, &mut Position>) {
+//!     for mut pos in query.iter_mut() {
 //!         let tmp = pos.x;
 //!         pos.x = pos.y;
 //!         pos.y = pos.z;
@@ -92,16 +89,16 @@
 //!     }
 //! }
 //!
-//! fn increment_system(iter: SingleComponentExclusiveIterMut<'_, Position>) {
-//!     for pos in iter {
+//! fn increment_system(mut query: Query<'_, &mut Position>) {
+//!     for mut pos in query.iter_mut() {
 //!         pos.x += 1.0;
 //!         pos.y += 2.0;
 //!         pos.z += 3.0;
 //!     }
 //! }
 //!
-//! fn print2_system(iter: PairComponentsRefIter<'_, Position, Velocity>) {
-//!     for (pos, vel) in iter {
+//! fn print2_system(query: Query<'_, (&Position, &Velocity)>) {
+//!     for (pos, vel) in query.iter() {
 //!         println!(
 //!             "Pos: [{}, {}, {}] Vel: [{}, {}, {}]",
 //!             pos.x, pos.y, pos.z, vel.x, vel.y, vel.z
@@ -109,8 +106,8 @@
 //!     }
 //! }
 //!
-//! fn apply_velocity_system(iter: PairComponentsRefIterMut<'_, Position, Velocity>) {
-//!     for (mut pos, vel) in iter {
+//! fn apply_velocity_system(mut query: Query<'_, (&mut Position, &Velocity)>) {
+//!     for (mut pos, vel) in query.iter_mut() {
 //!         pos.x += vel.x;
 //!         pos.y += vel.y;
 //!         pos.z += vel.z;
@@ -118,9 +115,20 @@
 //! }
 //! ```
 
+// derive マクロが生成する `::xanadu` のパスをこのクレートのテストでも解決できるようにする
+#[cfg(test)]
+extern crate self as xanadu;
+
+#[macro_use]
+mod macros;
+
 /// Collections to be used in ECS, but can be used independently.
 pub mod collections;
 
+mod error;
+
+pub use error::Error;
+
 /// ECS module; main module of this library.
 ///
 /// # ECS
@@ -132,7 +140,7 @@ pub mod collections;
 ///
 /// Entities are unique identifiers that have no intrinsic properties on their own. They are just
 /// identifiers used to keep track of components attached to them. In Xanadu, entities are just
-/// [`GenerationalId`](collections::GenerationalId)s.
+/// [`GenerationalId`](collections::GenerationalId)s, which are aliased as [`Entity`](ecs::Entity).
 ///
 /// # Components
 ///
@@ -142,6 +150,18 @@ pub mod collections;
 /// # Systems
 ///
 /// Systems are functions that operate on components. In Xanadu, systems are types which implement
-/// [`System`](ecs::System) trait. They are usually functions that take a reference to a component
-/// and return nothing.
+/// [`System`](ecs::System) trait. They are usually functions that take up to 16 parameters such as
+/// [`Query`](ecs::Query) and return nothing. The parameters of a system must not access the same
+/// component mutably.
+///
+/// # Resources
+///
+/// Resources are global values which are not attached to any entity, such as frame time or input
+/// state. Systems access them through [`Res`](ecs::Res) and [`ResMut`](ecs::ResMut) parameters.
+///
+/// # Commands
+///
+/// Systems cannot spawn or despawn entities while they borrow component storages. Instead, they
+/// record such changes with [`Commands`](ecs::Commands), which are applied after the system
+/// finishes.
 pub mod ecs;
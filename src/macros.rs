@@ -0,0 +1,10 @@
+/// Invoke `$m` for every suffix of the given identifiers, e.g. `(A, B, C)`, `(B, C)` and `(C)`.
+macro_rules! all_tuples {
+    ($m:ident, $first:ident) => {
+        $m!($first);
+    };
+    ($m:ident, $first:ident, $($rest:ident),*) => {
+        $m!($first, $($rest),*);
+        all_tuples!($m, $($rest),*);
+    };
+}